@@ -0,0 +1,58 @@
+/// Here is the benchmark of the storages of the exray - the same random workload is run with BoxStorage, RcStorage and ArenaStorage and their times are printed
+/// it is run with: cargo bench --bench storages [number of operations], the default is 200000 operations
+use extended_array::exray::{
+    ArenaStorage, BoxStorage, Exray, Max, NoAction, RcStorage, Storage, Sum,
+};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::env;
+use std::time::{Duration, Instant};
+
+/// runs random workload of inserts, erases, queries and reversals on exray with n elements and returns checksum of the results and the time of the workload
+/// the checksum is the same for all storages, so it shows that they did the same work
+fn run_workload<S: Storage<i64, (i64, i64), NoAction>>(n: usize, seed: u64) -> (i64, Duration) {
+    let mut rng = StdRng::seed_from_u64(seed);
+    let start = Instant::now();
+    let elements = (0..n as i64).collect::<Vec<_>>();
+    let mut e = Exray::<i64, (i64, i64), NoAction, (Sum<i64>, Max<i64>), S>::with_aggregate(
+        elements,
+        (Sum::new(), Max::new()),
+    );
+    let mut checksum = 0;
+    for i in 0..n {
+        let len = e.len();
+        let ind = rng.gen_range(0, len);
+        e.insert(ind, i as i64).unwrap();
+        e.erase(rng.gen_range(0, len + 1)).unwrap();
+        let beg = rng.gen_range(0, len);
+        let end = rng.gen_range(beg, len);
+        checksum += e.segment_aggregate_value(beg, end).unwrap().0;
+        if i % 64 == 0 {
+            e.reverse_segment(beg, end).unwrap();
+        }
+    }
+    for x in &e {
+        checksum ^= *x;
+    }
+    (checksum, start.elapsed())
+}
+
+fn main() {
+    // cargo bench passes --bench to the binary, so only the numeric argument is taken
+    let n = env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(200_000);
+    let results = vec![
+        ("Box", run_workload::<BoxStorage>(n, 7)),
+        ("Rc", run_workload::<RcStorage>(n, 7)),
+        (
+            "Arena",
+            run_workload::<ArenaStorage<i64, (i64, i64), NoAction>>(n, 7),
+        ),
+    ];
+    for (name, (checksum, time)) in &results {
+        assert_eq!(*checksum, (results[0].1).0);
+        println!("{} storage: {:.2?} for {} operations", name, time, n);
+    }
+}
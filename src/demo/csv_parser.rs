@@ -194,6 +194,26 @@ pub enum CsvError {
     InvalidRow(String),
 }
 
+use std::fmt;
+impl fmt::Display for CsvError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CsvError::IO(e) => write!(f, "reading error: {}", e),
+            CsvError::InvalidHeader(message) => write!(f, "invalid header: {}", message),
+            CsvError::InvalidRow(message) => write!(f, "invalid row: {}", message),
+        }
+    }
+}
+
+impl std::error::Error for CsvError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CsvError::IO(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
 use std::collections::{HashMap, HashSet};
 
 type Row = HashMap<String, String>;
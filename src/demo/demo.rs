@@ -68,7 +68,7 @@ impl FromStr for Element {
     }
 }
 
-use crate::exray::Exray;
+use extended_array::exray::Exray;
 
 use super::csv_parser::*;
 
@@ -77,6 +77,24 @@ pub enum DemoError {
     DataLoadError(Error),
     CsvError(CsvError),
 }
+
+impl Display for DemoError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            DemoError::DataLoadError(e) => write!(f, "the data can't be loaded: {}", e),
+            DemoError::CsvError(e) => write!(f, "the data can't be parsed: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for DemoError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            DemoError::DataLoadError(e) => Some(e),
+            DemoError::CsvError(e) => Some(e),
+        }
+    }
+}
 /// this is the main function which uses reqwest::blocking to download the csv data as string, then the csv_parser to parse it and in the end return either Vector with the data or DemoError
 pub fn get_data() -> Result<Vec<Element>, DemoError> {
     println!("Starting to load coronavirus data.");
@@ -150,9 +168,9 @@ pub fn get_data() -> Result<Vec<Element>, DemoError> {
 }
 
 // this functions is used by main to tell the segment where the country data is in the exray, we use binary searches on the country name because the data in the csv is sorted in ascending order of the country name and year_week
-pub fn find_country_segment(
+pub fn find_country_segment<U>(
     mut country: String,
-    exray: &Exray<Element, (f64, f64)>,
+    exray: &Exray<Element, U>,
 ) -> Option<(usize, usize)> {
     country = country.to_lowercase();
     let mut l = -1;
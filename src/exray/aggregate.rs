@@ -0,0 +1,388 @@
+/// Here are the functions that the treap maintains - the trait Aggregate and the types implementing it
+use std::borrow::Cow;
+use std::marker::PhantomData;
+use std::ops::Add;
+use std::rc::Rc;
+
+/// this trait is for the functions that the treap maintains, every aggregate should have:
+/// leaf value - the value of the function for segment with one element
+/// combine - the value for two neighbouring segments from their values and number of elements
+/// identity - the value for empty segment, combining it with some other value should not change it
+/// stable identifier - aggregates with the same identifier should compute the same values, so exrays with them are compatible
+pub trait Aggregate<T> {
+    type Value;
+
+    fn leaf(&self, value: &T) -> Self::Value;
+    /// the first parameter is the left segment and the second is the right segment, with their values and number of elements
+    fn combine(&self, l: (&Self::Value, u64), r: (&Self::Value, u64)) -> Self::Value;
+    fn identity(&self) -> Self::Value;
+    /// the identifier is borrowed for the simple aggregates and built for the tuples of aggregates
+    fn id(&self) -> Cow<'_, str>;
+
+    /// non-commutative aggregates depend on the order of the elements, for them the treap maintains also the values for the reversed segments
+    fn is_commutative(&self) -> bool {
+        true
+    }
+
+    /// computes the value for a node of the treap from the values of the left part, the element in the node and the right part
+    /// it can be overridden if there is a faster way than combining the leaf value with the neighbours
+    fn compute(
+        &self,
+        l: Option<(&Self::Value, u64)>,
+        value: &T,
+        r: Option<(&Self::Value, u64)>,
+    ) -> Self::Value {
+        let mut result = self.leaf(value);
+        let mut cnt = 1;
+        if let Some(l) = l {
+            result = self.combine(l, (&result, cnt));
+            cnt += l.1;
+        }
+        if let Some(r) = r {
+            result = self.combine((&result, cnt), r);
+        }
+        result
+    }
+}
+
+/// this type is the default for the functions of the exray, the aggregates are behind Rc so they can be shared between exrays and stored in HashMaps
+pub type Func<T, U> = Rc<dyn Aggregate<T, Value = U>>;
+
+impl<T, G: Aggregate<T> + ?Sized> Aggregate<T> for Rc<G> {
+    type Value = G::Value;
+
+    fn leaf(&self, value: &T) -> Self::Value {
+        (**self).leaf(value)
+    }
+    fn combine(&self, l: (&Self::Value, u64), r: (&Self::Value, u64)) -> Self::Value {
+        (**self).combine(l, r)
+    }
+    fn identity(&self) -> Self::Value {
+        (**self).identity()
+    }
+    fn id(&self) -> Cow<'_, str> {
+        (**self).id()
+    }
+    fn is_commutative(&self) -> bool {
+        (**self).is_commutative()
+    }
+    fn compute(
+        &self,
+        l: Option<(&Self::Value, u64)>,
+        value: &T,
+        r: Option<(&Self::Value, u64)>,
+    ) -> Self::Value {
+        (**self).compute(l, value, r)
+    }
+}
+
+/// FnAggregate makes aggregate from closures, so they can capture parameters like modulus or threshold
+/// the parameters should be part of the id, because aggregates with different parameters compute different values
+pub struct FnAggregate<L, C, I> {
+    id: String,
+    leaf: L,
+    combine: C,
+    identity: I,
+    commutative: bool,
+}
+
+impl<L, C, I> FnAggregate<L, C, I> {
+    pub fn new(id: &str, leaf: L, combine: C, identity: I) -> Self {
+        FnAggregate {
+            id: id.to_string(),
+            leaf: leaf,
+            combine: combine,
+            identity: identity,
+            commutative: true,
+        }
+    }
+
+    /// marks the aggregate as non-commutative
+    pub fn non_commutative(mut self) -> Self {
+        self.commutative = false;
+        self
+    }
+}
+
+impl<T, V, L, C, I> Aggregate<T> for FnAggregate<L, C, I>
+where
+    L: Fn(&T) -> V,
+    C: Fn((&V, u64), (&V, u64)) -> V,
+    I: Fn() -> V,
+{
+    type Value = V;
+
+    fn leaf(&self, value: &T) -> V {
+        (self.leaf)(value)
+    }
+    fn combine(&self, l: (&V, u64), r: (&V, u64)) -> V {
+        (self.combine)(l, r)
+    }
+    fn identity(&self) -> V {
+        (self.identity)()
+    }
+    fn id(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.id)
+    }
+    fn is_commutative(&self) -> bool {
+        self.commutative
+    }
+}
+
+/// this trait gives the identity values for Max and Min - the smallest and the largest value of the type
+pub trait Bounded {
+    fn min_value() -> Self;
+    fn max_value() -> Self;
+}
+
+macro_rules! impl_bounded {
+    ($($t:ty),*) => {
+        $(
+            impl Bounded for $t {
+                fn min_value() -> Self {
+                    <$t>::MIN
+                }
+                fn max_value() -> Self {
+                    <$t>::MAX
+                }
+            }
+        )*
+    };
+}
+impl_bounded!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+impl Bounded for f32 {
+    fn min_value() -> Self {
+        f32::NEG_INFINITY
+    }
+    fn max_value() -> Self {
+        f32::INFINITY
+    }
+}
+impl Bounded for f64 {
+    fn min_value() -> Self {
+        f64::NEG_INFINITY
+    }
+    fn max_value() -> Self {
+        f64::INFINITY
+    }
+}
+
+/// Sum, Max, Min and Avg are typed aggregates over a key of the elements
+/// with new the key is the element itself and with by the key is computed by the function key, name is the name of the key and it is part of the id
+/// they can be combined in tuples, so every aggregate keeps its own value type
+pub struct Sum<K, G = fn(&K) -> K> {
+    id: String,
+    key: G,
+    marker: PhantomData<fn() -> K>,
+}
+
+pub struct Max<K, G = fn(&K) -> K> {
+    id: String,
+    key: G,
+    marker: PhantomData<fn() -> K>,
+}
+
+pub struct Min<K, G = fn(&K) -> K> {
+    id: String,
+    key: G,
+    marker: PhantomData<fn() -> K>,
+}
+
+/// Avg maintains the average of the keys as f64, empty segments have average 0
+pub struct Avg<G = fn(&f64) -> f64> {
+    id: String,
+    key: G,
+}
+
+macro_rules! impl_keyed_constructors {
+    ($name:ident, $id:expr) => {
+        impl<K: Clone> $name<K> {
+            pub fn new() -> Self {
+                $name {
+                    id: String::from($id),
+                    key: K::clone,
+                    marker: PhantomData,
+                }
+            }
+        }
+
+        impl<K: Clone> Default for $name<K> {
+            fn default() -> Self {
+                Self::new()
+            }
+        }
+
+        impl<K, G> $name<K, G> {
+            pub fn by(name: &str, key: G) -> Self {
+                $name {
+                    id: format!("{}({})", $id, name),
+                    key: key,
+                    marker: PhantomData,
+                }
+            }
+        }
+    };
+}
+impl_keyed_constructors!(Sum, "sum");
+impl_keyed_constructors!(Max, "max");
+impl_keyed_constructors!(Min, "min");
+
+impl<T, K, G> Aggregate<T> for Sum<K, G>
+where
+    K: Add<Output = K> + Default + Clone,
+    G: Fn(&T) -> K,
+{
+    type Value = K;
+
+    fn leaf(&self, value: &T) -> K {
+        (self.key)(value)
+    }
+    fn combine(&self, l: (&K, u64), r: (&K, u64)) -> K {
+        l.0.clone() + r.0.clone()
+    }
+    fn identity(&self) -> K {
+        K::default()
+    }
+    fn id(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.id)
+    }
+}
+
+impl<T, K, G> Aggregate<T> for Max<K, G>
+where
+    K: PartialOrd + Bounded + Clone,
+    G: Fn(&T) -> K,
+{
+    type Value = K;
+
+    fn leaf(&self, value: &T) -> K {
+        (self.key)(value)
+    }
+    fn combine(&self, l: (&K, u64), r: (&K, u64)) -> K {
+        if r.0 > l.0 {
+            return r.0.clone();
+        }
+        return l.0.clone();
+    }
+    fn identity(&self) -> K {
+        K::min_value()
+    }
+    fn id(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.id)
+    }
+}
+
+impl<T, K, G> Aggregate<T> for Min<K, G>
+where
+    K: PartialOrd + Bounded + Clone,
+    G: Fn(&T) -> K,
+{
+    type Value = K;
+
+    fn leaf(&self, value: &T) -> K {
+        (self.key)(value)
+    }
+    fn combine(&self, l: (&K, u64), r: (&K, u64)) -> K {
+        if r.0 < l.0 {
+            return r.0.clone();
+        }
+        return l.0.clone();
+    }
+    fn identity(&self) -> K {
+        K::max_value()
+    }
+    fn id(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.id)
+    }
+}
+
+impl Avg {
+    pub fn new() -> Self {
+        Avg {
+            id: String::from("avg"),
+            key: |x: &f64| -> f64 { *x },
+        }
+    }
+}
+
+impl Default for Avg {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<G> Avg<G> {
+    pub fn by(name: &str, key: G) -> Self {
+        Avg {
+            id: format!("avg({})", name),
+            key: key,
+        }
+    }
+}
+
+impl<T, G: Fn(&T) -> f64> Aggregate<T> for Avg<G> {
+    type Value = f64;
+
+    fn leaf(&self, value: &T) -> f64 {
+        (self.key)(value)
+    }
+    fn combine(&self, l: (&f64, u64), r: (&f64, u64)) -> f64 {
+        let l_cnt = l.1 as f64;
+        let r_cnt = r.1 as f64;
+        if l.1 + r.1 == 0 {
+            return 0.;
+        }
+        return (*l.0 * l_cnt + *r.0 * r_cnt) / (l_cnt + r_cnt);
+    }
+    fn identity(&self) -> f64 {
+        0.
+    }
+    fn id(&self) -> Cow<'_, str> {
+        Cow::Borrowed(&self.id)
+    }
+}
+
+/// tuples of aggregates are aggregates with tuple of their values, the tuple is non-commutative if any of its parts is
+macro_rules! impl_tuple_aggregate {
+    ($(($G:ident, $i:tt)),+) => {
+        impl<T, $($G: Aggregate<T>),+> Aggregate<T> for ($($G,)+) {
+            type Value = ($($G::Value,)+);
+
+            fn leaf(&self, value: &T) -> Self::Value {
+                ($(self.$i.leaf(value),)+)
+            }
+            fn combine(&self, l: (&Self::Value, u64), r: (&Self::Value, u64)) -> Self::Value {
+                ($(self.$i.combine((&(l.0).$i, l.1), (&(r.0).$i, r.1)),)+)
+            }
+            fn identity(&self) -> Self::Value {
+                ($(self.$i.identity(),)+)
+            }
+            fn id(&self) -> Cow<'_, str> {
+                let ids = [$(self.$i.id()),+];
+                Cow::Owned(format!("({})", ids.join(",")))
+            }
+            fn is_commutative(&self) -> bool {
+                true $(&& self.$i.is_commutative())+
+            }
+            fn compute(
+                &self,
+                l: Option<(&Self::Value, u64)>,
+                value: &T,
+                r: Option<(&Self::Value, u64)>,
+            ) -> Self::Value {
+                ($(self.$i.compute(
+                    l.map(|(v, cnt)| (&v.$i, cnt)),
+                    value,
+                    r.map(|(v, cnt)| (&v.$i, cnt)),
+                ),)+)
+            }
+        }
+    };
+}
+impl_tuple_aggregate!((A0, 0));
+impl_tuple_aggregate!((A0, 0), (A1, 1));
+impl_tuple_aggregate!((A0, 0), (A1, 1), (A2, 2));
+impl_tuple_aggregate!((A0, 0), (A1, 1), (A2, 2), (A3, 3));
+impl_tuple_aggregate!((A0, 0), (A1, 1), (A2, 2), (A3, 3), (A4, 4));
+impl_tuple_aggregate!((A0, 0), (A1, 1), (A2, 2), (A3, 3), (A4, 4), (A5, 5));
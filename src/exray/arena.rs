@@ -0,0 +1,203 @@
+/// Here is the arena storage for the implicit treap - the nodes are in one contiguous Vector and they are addressed by u32 indices
+use crate::exray::treap::{ImplicitTreap, Link, Storage, Values};
+use std::mem;
+use std::ops::{Deref, DerefMut};
+use std::slice;
+
+/// the container for the functions' values in the nodes of the arena, the common case of one function (usually a tuple of aggregates) is kept inline in the node without separate allocation
+#[derive(Default)]
+pub enum InlineValues<U> {
+    #[default]
+    Empty,
+    One(U),
+    Many(Vec<U>),
+}
+
+impl<U> Deref for InlineValues<U> {
+    type Target = [U];
+
+    fn deref(&self) -> &[U] {
+        match self {
+            InlineValues::Empty => &[],
+            InlineValues::One(value) => slice::from_ref(value),
+            InlineValues::Many(values) => values,
+        }
+    }
+}
+
+impl<U> DerefMut for InlineValues<U> {
+    fn deref_mut(&mut self) -> &mut [U] {
+        match self {
+            InlineValues::Empty => &mut [],
+            InlineValues::One(value) => slice::from_mut(value),
+            InlineValues::Many(values) => values,
+        }
+    }
+}
+
+impl<U> Values<U> for InlineValues<U> {
+    fn push(&mut self, value: U) {
+        *self = match mem::take(self) {
+            InlineValues::Empty => InlineValues::One(value),
+            InlineValues::One(first) => InlineValues::Many(vec![first, value]),
+            InlineValues::Many(mut values) => {
+                values.push(value);
+                InlineValues::Many(values)
+            }
+        };
+    }
+    // the memory of Many is kept, so recovering a node with many functions doesn't allocate
+    fn clear(&mut self) {
+        match self {
+            InlineValues::Many(values) => values.clear(),
+            _ => *self = InlineValues::Empty,
+        }
+    }
+    fn remove(&mut self, ind: usize) -> U {
+        match mem::take(self) {
+            InlineValues::One(value) if ind == 0 => value,
+            InlineValues::Many(mut values) => {
+                let value = values.remove(ind);
+                *self = InlineValues::Many(values);
+                value
+            }
+            _ => panic!("No value with index {} in the node!", ind),
+        }
+    }
+    // only the values of Many are outside of the node
+    fn heap_bytes(&self) -> usize {
+        match self {
+            InlineValues::Many(values) => values.capacity() * mem::size_of::<U>(),
+            _ => 0,
+        }
+    }
+}
+
+/// a slot of the arena is either used by a node or free, the free slots are linked in a list by the index of the next free slot
+enum Slot<T, U, A> {
+    Used(ImplicitTreap<T, U, A, ArenaStorage<T, U, A>>),
+    Free(Option<u32>),
+}
+
+/// this is the storage where all nodes of the exray are in one Vector, which is better for the cache and the memory than a Box for every node
+/// the slots of the freed nodes are reused by the next allocations, the exrays with this storage don't have iter_mut because the nodes can't be borrowed without the storage
+/// every exray has its own arena, so the operations that move nodes between exrays copy them to the other arena in time linear in the number of moved nodes
+pub struct ArenaStorage<T, U, A> {
+    /// the slots with the nodes
+    slots: Vec<Slot<T, U, A>>,
+    /// the index of the first free slot
+    free: Option<u32>,
+}
+
+impl<T, U, A> Default for ArenaStorage<T, U, A> {
+    fn default() -> Self {
+        ArenaStorage::<T, U, A> {
+            slots: Vec::new(),
+            free: None,
+        }
+    }
+}
+
+impl<T, U, A> ArenaStorage<T, U, A> {
+    /// returns the number of slots in the arena, the used and the free ones
+    pub fn capacity(&self) -> usize {
+        self.slots.len()
+    }
+
+    fn node(&self, ind: u32) -> &ImplicitTreap<T, U, A, Self> {
+        match self.slots[ind as usize] {
+            Slot::Used(ref node) => node,
+            Slot::Free(_) => panic!("Node in free slot of the arena!"),
+        }
+    }
+    fn node_mut(&mut self, ind: u32) -> &mut ImplicitTreap<T, U, A, Self> {
+        match self.slots[ind as usize] {
+            Slot::Used(ref mut node) => node,
+            Slot::Free(_) => panic!("Node in free slot of the arena!"),
+        }
+    }
+}
+
+impl<T, U, A> Storage<T, U, A> for ArenaStorage<T, U, A> {
+    type Ptr = u32;
+    type Values = InlineValues<U>;
+
+    fn alloc(&mut self, node: ImplicitTreap<T, U, A, Self>) -> Self::Ptr {
+        if self.free.is_some() {
+            let ind = self.free.unwrap();
+            let slot = mem::replace(&mut self.slots[ind as usize], Slot::Used(node));
+            match slot {
+                Slot::Free(next) => self.free = next,
+                Slot::Used(_) => panic!("Used slot in the free list of the arena!"),
+            }
+            return ind;
+        }
+        if self.slots.len() >= u32::MAX as usize {
+            panic!("Too many nodes for the arena!");
+        }
+        self.slots.push(Slot::Used(node));
+        return (self.slots.len() - 1) as u32;
+    }
+    fn get<'a>(&'a self, ptr: &'a Self::Ptr) -> &'a ImplicitTreap<T, U, A, Self> {
+        self.node(*ptr)
+    }
+    fn get_mut<'a>(&'a mut self, ptr: &'a mut Self::Ptr) -> &'a mut ImplicitTreap<T, U, A, Self> {
+        self.node_mut(*ptr)
+    }
+    fn get_mut_path<'a>(
+        &'a mut self,
+        ptr: &'a mut Self::Ptr,
+        path: &[bool],
+    ) -> &'a mut ImplicitTreap<T, U, A, Self> {
+        let mut ind = *ptr;
+        for &right in path {
+            let node = self.node(ind);
+            let next = if right { node.r } else { node.l };
+            ind = next.unwrap();
+        }
+        return self.node_mut(ind);
+    }
+    fn free(&mut self, ptr: Self::Ptr) -> Option<ImplicitTreap<T, U, A, Self>> {
+        Some(self.take(ptr))
+    }
+    fn take(&mut self, ptr: Self::Ptr) -> ImplicitTreap<T, U, A, Self> {
+        let slot = mem::replace(&mut self.slots[ptr as usize], Slot::Free(self.free));
+        self.free = Some(ptr);
+        match slot {
+            Slot::Used(node) => node,
+            Slot::Free(_) => panic!("Node in free slot of the arena!"),
+        }
+    }
+
+    /// the nodes are taken out of the other arena one by one and attached to their new parents, which are already moved
+    /// so unlike the pointer storages moving a tree of M nodes takes O(M) time
+    fn move_tree(&mut self, curr: Link<T, U, A, Self>, other: &mut Self) -> Link<T, U, A, Self> {
+        let curr = curr?;
+        let mut root = None;
+        // every node waiting to be moved has the new index of its parent and the side of it - true for right
+        let mut stack = vec![(curr, None, false)];
+        while let Some((old_ind, parent, right)) = stack.pop() {
+            let mut node = other.take(old_ind);
+            let l = node.l.take();
+            let r = node.r.take();
+            let ind = self.alloc(node);
+            match parent {
+                None => root = Some(ind),
+                Some(parent) => {
+                    if right {
+                        self.node_mut(parent).r = Some(ind);
+                    } else {
+                        self.node_mut(parent).l = Some(ind);
+                    }
+                }
+            }
+            if let Some(l) = l {
+                stack.push((l, Some(ind), false));
+            }
+            if let Some(r) = r {
+                stack.push((r, Some(ind), true));
+            }
+        }
+        return root;
+    }
+}
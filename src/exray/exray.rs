@@ -1,5 +1,21 @@
 /// This file is for the implementation of exray structure that has public interface and is based on the implicit treap in treap.rs
+use crate::exray::aggregate::*;
+use crate::exray::arena::*;
+use crate::exray::guard::*;
+use crate::exray::inspect::*;
+use crate::exray::iter::*;
 use crate::exray::treap::*;
+use crate::exray::view::*;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::cell::RefCell;
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Debug;
+use std::iter::FromIterator;
+use std::mem;
+use std::ops::{Bound, Range, RangeBounds};
 #[cfg(test)]
 mod tests {
     #[test]
@@ -8,7 +24,12 @@ mod tests {
     }
 
     use super::*;
-    use std::cmp;
+    use crate::exray::registry::FunctionRegistry;
+    use crate::exray::sorted::SortedExray;
+    use crate::exray::testing::*;
+    use std::panic;
+    use std::panic::AssertUnwindSafe;
+    use std::rc::Rc;
     macro_rules! assert_match {
         ($expr:expr, $pat:pat) => {
             if let $pat = $expr {
@@ -34,35 +55,13 @@ mod tests {
     }
     #[test]
     fn test_insert() {
-        let mut e = Exray::<i64, i64>::new(
-            vec![],
-            vec![
-                |x: Option<(&i64, u64)>, y: &i64, z: Option<(&i64, u64)>| -> i64 {
-                    match x {
-                        None => match z {
-                            None => *y * 2,
-                            Some(z) => (*y) * 2 + *z.0,
-                        },
-                        Some(x) => match z {
-                            None => *x.0 + (*y) * 2,
-                            Some(z) => *x.0 + (*y) * 2 + *z.0,
-                        },
-                    }
-                },
-                |x: Option<(&i64, u64)>, y: &i64, z: Option<(&i64, u64)>| -> i64 {
-                    match x {
-                        None => match z {
-                            None => (*y),
-                            Some(z) => cmp::min(*y, *z.0),
-                        },
-                        Some(x) => match z {
-                            None => cmp::min(*x.0, *y),
-                            Some(z) => cmp::min(cmp::min(*x.0, *y), *z.0),
-                        },
-                    }
-                },
-            ],
-        );
+        let double_sum: Func<i64, i64> = Rc::new(FnAggregate::new(
+            "double_sum",
+            |y: &i64| -> i64 { *y * 2 },
+            |x: (&i64, u64), z: (&i64, u64)| -> i64 { *x.0 + *z.0 },
+            || -> i64 { 0 },
+        ));
+        let mut e = Exray::<i64, i64>::new(vec![], vec![double_sum, min()]);
         assert_match!(e.insert(0, 2).err(), None);
         assert_match!(e.insert(0, 1).err(), None);
         assert_match!(e.insert(2, 3).err(), None);
@@ -71,24 +70,23 @@ mod tests {
         assert_eq!(exray_to_vec(&e), vec![1, 2, 3, 4]);
         assert_eq!(e.functions_values(), vec![20, 1]);
 
-        assert_match!(e.insert(5, 1).err(), Some(ExrayError::IndexError(_)));
+        assert_match!(
+            e.insert(5, 1).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
     }
 
-    fn add(x: Option<(&i64, u64)>, y: &i64, z: Option<(&i64, u64)>) -> i64 {
-        match x {
-            None => match z {
-                None => *y,
-                Some(z) => *y + *z.0,
-            },
-            Some(x) => match z {
-                None => *x.0 + *y,
-                Some(z) => *x.0 + *y + *z.0,
-            },
-        }
+    fn add() -> Func<i64, i64> {
+        return Rc::new(FnAggregate::new(
+            "sum",
+            |y: &i64| -> i64 { *y },
+            |x: (&i64, u64), z: (&i64, u64)| -> i64 { *x.0 + *z.0 },
+            || -> i64 { 0 },
+        ));
     }
     #[test]
     fn test_erase() {
-        let mut e = Exray::<i64, i64>::new(vec![], vec![add]);
+        let mut e = Exray::<i64, i64>::new(vec![], vec![add()]);
         assert_match!(e.insert(0, 2).err(), None);
         assert_match!(e.insert(0, 1).err(), None);
         assert_match!(e.insert(2, 3).err(), None);
@@ -107,7 +105,7 @@ mod tests {
         assert_eq!(e.functions_values(), vec![2]);
 
         assert_match!(e.erase(0).err(), None);
-        assert_eq!(exray_to_vec(&e), vec![]);
+        assert_eq!(exray_to_vec(&e), Vec::<i64>::new());
 
         assert_match!(e.insert(0, 2).err(), None);
         assert_match!(e.insert(0, 1).err(), None);
@@ -116,7 +114,7 @@ mod tests {
 
         assert_eq!(exray_to_vec(&e), vec![1, 2, 3, 4]);
         assert_eq!(e.functions_values(), vec![10]);
-        assert_match!(e.erase(4).err(), Some(ExrayError::IndexError(_)));
+        assert_match!(e.erase(4).err(), Some(ExrayError::IndexOutOfBounds { .. }));
 
         assert_match!(e.erase_segment(1, 2).err(), None);
         assert_eq!(exray_to_vec(&e), vec![1, 4]);
@@ -126,16 +124,22 @@ mod tests {
         assert_eq!(exray_to_vec(&e), vec![1]);
         assert_eq!(e.functions_values(), vec![1]);
 
-        assert_match!(e.erase_segment(1, 1).err(), Some(ExrayError::IndexError(_)));
-        assert_match!(e.erase_segment(1, 0).err(), Some(ExrayError::IndexError(_)));
+        assert_match!(
+            e.erase_segment(1, 1).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+        assert_match!(
+            e.erase_segment(1, 0).err(),
+            Some(ExrayError::InvalidRange { .. })
+        );
 
         assert_match!(e.erase_segment(0, 0).err(), None);
-        assert_eq!(exray_to_vec(&e), vec![]);
+        assert_eq!(exray_to_vec(&e), Vec::<i64>::new());
     }
 
     #[test]
     fn test_other_segment_fns() {
-        let mut e = Exray::<i64, i64>::new(vec![], vec![add]);
+        let mut e = Exray::<i64, i64>::new(vec![], vec![add()]);
         assert_match!(e.insert(0, 2).err(), None);
         assert_match!(e.insert(0, 1).err(), None);
         assert_match!(e.insert(2, 9).err(), None);
@@ -169,37 +173,28 @@ mod tests {
 
         assert_match!(
             e.segment_functions_values(1, 4).err(),
-            Some(ExrayError::IndexError(_))
+            Some(ExrayError::IndexOutOfBounds { .. })
         );
         assert_match!(
             e.segment_functions_values(2, 1).err(),
-            Some(ExrayError::IndexError(_))
-        );
-
-        let mut fail_e = Exray::<i64, i64>::new(
-            vec![],
-            vec![
-                |x: Option<(&i64, u64)>, y: &i64, z: Option<(&i64, u64)>| -> i64 {
-                    match x {
-                        None => match z {
-                            None => *y,
-                            Some(z) => *y + *z.0,
-                        },
-                        Some(x) => match z {
-                            None => *x.0 + *y,
-                            Some(z) => *x.0 + *y + *z.0,
-                        },
-                    }
-                },
-            ],
+            Some(ExrayError::InvalidRange { .. })
         );
+
+        // the same computation under a different id is a different aggregate
+        let other_sum: Func<i64, i64> = Rc::new(FnAggregate::new(
+            "other_sum",
+            |y: &i64| -> i64 { *y },
+            |x: (&i64, u64), z: (&i64, u64)| -> i64 { *x.0 + *z.0 },
+            || -> i64 { 0 },
+        ));
+        let mut fail_e = Exray::<i64, i64>::new(vec![], vec![other_sum]);
         let err2 = e.insert_exray(&mut fail_e, 0);
         assert_match!(
             err2.as_ref().err(),
-            Some(ExrayError::IncompatibleExrayError(_))
+            Some(ExrayError::FunctionMismatch { .. })
         );
 
-        let mut last_e = Exray::<i64, i64>::new(vec![], vec![add]);
+        let mut last_e = Exray::<i64, i64>::new(vec![], vec![add()]);
         assert_match!(last_e.insert(0, 42).err(), None);
         let err3 = e.insert_exray(&mut last_e, 0); // array should be: [42, 1, 2, 3, 9]
         assert_match!(err3.as_ref().err(), None);
@@ -209,7 +204,7 @@ mod tests {
 
     #[test]
     fn test_clone_segment() {
-        let mut e = Exray::<i64, i64>::new(vec![], vec![add]);
+        let mut e = Exray::<i64, i64>::new(vec![], vec![add()]);
         assert_match!(e.insert(0, 2).err(), None);
         assert_match!(e.insert(0, 1).err(), None);
         assert_match!(e.insert(2, 4).err(), None);
@@ -230,394 +225,2760 @@ mod tests {
         assert_eq!(exray_to_vec(&e), vec![1, 2, 4, 9]);
         assert_eq!(e.functions_values(), vec![16]);
 
-        assert_match!(e.clone_segment(2, 1).err(), Some(ExrayError::IndexError(_)));
-        assert_match!(e.clone_segment(2, 4).err(), Some(ExrayError::IndexError(_)));
+        assert_match!(
+            e.clone_segment(2, 1).err(),
+            Some(ExrayError::InvalidRange { .. })
+        );
+        assert_match!(
+            e.clone_segment(2, 4).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
 
         let err = e.insert_exray(&mut cloned, 1);
         assert_match!(err.as_ref().err(), None);
         assert_eq!(exray_to_vec(&e), vec![1, 2, 4, 2, 4, 9]);
     }
-}
-
-/// Exray name comes from the beginning and ending of extended-array :)
-pub struct Exray<T, U> {
-    /// link to the root of the implicit treap
-    root: Link<T, U>,
-    /// Vector containing the functions that are maintained by the treap
-    functions: Vec<Func<T, U>>,
-}
-#[derive(Debug)]
-pub enum ExrayError {
-    IndexError(String),
-    IncompatibleExrayError(String),
-}
 
-impl<T, U> Exray<T, U> {
-    /// function for making exray with values from the Vector elements and maintaining the functions in Vector functions
-    pub fn new(elements: Vec<T>, functions: Vec<Func<T, U>>) -> Self {
-        let mut exray = Exray::<T, U> {
-            root: None,
-            functions: functions,
-        };
-        for element in elements {
-            let mut value_all = Vec::<U>::new();
-            for function in exray.functions.iter() {
-                value_all.push(function(None, &element, None));
+    fn min() -> Func<i64, i64> {
+        return Rc::new(FnAggregate::new(
+            "min",
+            |y: &i64| -> i64 { *y },
+            |x: (&i64, u64), z: (&i64, u64)| -> i64 { cmp::min(*x.0, *z.0) },
+            || -> i64 { i64::MAX },
+        ));
+    }
+    /// action for the tests with add and min functions
+    #[derive(Debug, Clone, PartialEq)]
+    enum Change {
+        Add(i64),
+        Assign(i64),
+    }
+    impl Action<i64, i64> for Change {
+        fn apply_value(&self, value: &mut i64) {
+            match self {
+                Change::Add(d) => *value += d,
+                Change::Assign(x) => *value = *x,
             }
-            let mut new_treap = make_treap(element, value_all);
-            let mut temp = None;
-            merge(&mut temp, &mut exray.root, &mut new_treap, &exray.functions);
-            exray.root = temp;
         }
-        return exray;
+        fn apply_fvalue(&self, ind: usize, fvalue: &mut i64, cnt: u64) {
+            match (ind, self) {
+                (0, Change::Add(d)) => *fvalue += d * (cnt as i64),
+                (0, Change::Assign(x)) => *fvalue = x * (cnt as i64),
+                (_, Change::Add(d)) => *fvalue += d,
+                (_, Change::Assign(x)) => *fvalue = *x,
+            }
+        }
+        fn compose(&mut self, newer: &Self) {
+            *self = match (&*self, newer) {
+                (Change::Add(d1), Change::Add(d2)) => Change::Add(d1 + d2),
+                (Change::Assign(x), Change::Add(d)) => Change::Assign(x + d),
+                (_, Change::Assign(x)) => Change::Assign(*x),
+            };
+        }
     }
-
-    pub fn len(&self) -> usize {
-        get_cnt(&self.root) as usize
+    fn lazy_exray_to_vec(e: &mut Exray<i64, i64, Change>) -> Vec<i64> {
+        let mut res = Vec::<i64>::new();
+        let len = e.len();
+        for i in 0..len {
+            res.push(*e.get_element(i).unwrap());
+        }
+        res
     }
+    #[test]
+    fn test_update_segment() {
+        let mut e = Exray::<i64, i64, Change>::new(vec![5, 1, 4, 2, 8, 3], vec![add(), min()]);
+
+        assert_match!(e.update_segment(1, 3, Change::Add(10)).err(), None);
+        assert_eq!(lazy_exray_to_vec(&mut e), vec![5, 11, 14, 12, 8, 3]);
+        assert_eq!(e.functions_values(), vec![53, 3]);
+
+        assert_match!(e.update_segment(3, 5, Change::Assign(-1)).err(), None);
+        assert_match!(e.update_segment(0, 4, Change::Add(2)).err(), None);
+        assert_eq!(e.functions_values(), vec![37, -1]);
+        // the ends are read with the pending actions applied
+        assert_eq!(e.first(), Some(&7));
+        assert_eq!(e.last(), Some(&-1));
+        let r = e.segment_functions_values(2, 5);
+        assert_match!(r.as_ref().err(), None);
+        assert_eq!(r.unwrap(), vec![17, -1]);
+        assert_eq!(lazy_exray_to_vec(&mut e), vec![7, 13, 16, 1, 1, -1]);
 
-    pub fn functions(&self) -> &[Func<T, U>] {
-        &self.functions
-    }
+        let r = e.extract_segment(1, 3);
+        assert_match!(r.as_ref().err(), None);
+        let mut new_e = r.unwrap();
+        assert_match!(new_e.update_segment(0, 2, Change::Add(-1)).err(), None);
+        assert_eq!(new_e.functions_values(), vec![27, 0]);
+        assert_match!(e.insert_exray(&mut new_e, 0).err(), None);
+        assert_eq!(lazy_exray_to_vec(&mut e), vec![12, 15, 0, 7, 1, -1]);
+        assert_eq!(e.functions_values(), vec![34, -1]);
 
-    /// inserts value at ind in exray
-    pub fn insert(&mut self, ind: usize, value: T) -> Result<(), ExrayError> {
-        if self.len() < ind {
-            return Err(ExrayError::IndexError(String::from(
-                "Index greater than size!",
-            )));
-        }
+        assert_match!(e.erase(5).err(), None);
+        assert_eq!(e.functions_values(), vec![35, 0]);
 
-        let mut l_part = None;
-        let mut r_part = None;
-        split(
-            &mut self.root,
-            ind as u64,
-            &mut l_part,
-            &mut r_part,
-            &self.functions,
+        assert_match!(
+            e.update_segment(2, 5, Change::Add(1)).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
         );
+        assert_match!(
+            e.update_segment(2, 1, Change::Add(1)).err(),
+            Some(ExrayError::InvalidRange { .. })
+        );
+    }
 
-        let mut value_all = Vec::<U>::new();
-        for function in self.functions.iter() {
-            value_all.push(function(None, &value, None));
-        }
-        let mut new_treap = make_treap(value, value_all);
-        let mut temp = None;
-        merge(&mut temp, &mut l_part, &mut new_treap, &self.functions);
-        merge(&mut self.root, &mut temp, &mut r_part, &self.functions);
-
-        return Ok(());
+    fn first() -> Func<i64, i64> {
+        return Rc::new(
+            FnAggregate::new(
+                "first",
+                |y: &i64| -> i64 { *y },
+                |x: (&i64, u64), _z: (&i64, u64)| -> i64 { *x.0 },
+                || -> i64 { 0 },
+            )
+            .non_commutative(),
+        );
     }
+    #[test]
+    fn test_reverse_segment() {
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3, 4, 5, 6], vec![add(), first()]);
+        assert_eq!(e.non_commutative(), &[1]);
+
+        assert_match!(e.reverse_segment(1, 4).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![1, 5, 4, 3, 2, 6]);
+        assert_eq!(e.to_vec(), vec![&1, &5, &4, &3, &2, &6]);
+        assert_eq!(e.functions_values(), vec![21, 1]);
+        let r = e.segment_functions_values(1, 3);
+        assert_match!(r.as_ref().err(), None);
+        assert_eq!(r.unwrap(), vec![12, 5]);
 
-    /// erases the element at ind
-    pub fn erase(&mut self, ind: usize) -> Result<(), ExrayError> {
-        if self.len() <= ind {
-            return Err(ExrayError::IndexError(String::from(
-                "Index greater than last index!",
-            )));
-        }
+        assert_match!(e.reverse_segment(0, 5).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![6, 2, 3, 4, 5, 1]);
+        assert_eq!(e.functions_values(), vec![21, 6]);
 
-        let mut l_part = None;
-        let mut r_part = None;
-        split(
-            &mut self.root,
-            ind as u64,
-            &mut l_part,
-            &mut r_part,
-            &self.functions,
+        assert_match!(e.erase(0).err(), None);
+        assert_match!(e.insert(3, 7).err(), None);
+        assert_match!(e.reverse_segment(2, 2).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![2, 3, 4, 7, 5, 1]);
+        assert_eq!(e.functions_values(), vec![22, 2]);
+
+        let mut cloned = e.clone_segment(3, 5).unwrap();
+        assert_match!(cloned.reverse_segment(0, 2).err(), None);
+        assert_eq!(cloned.functions_values(), vec![13, 1]);
+        assert_match!(e.insert_exray(&mut cloned, 0).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![1, 5, 7, 2, 3, 4, 7, 5, 1]);
+        assert_eq!(e.functions_values(), vec![35, 1]);
+
+        let mut other = Exray::<i64, i64>::new(vec![1], vec![add(), min()]);
+        assert_match!(
+            e.insert_exray(&mut other, 0).err(),
+            Some(ExrayError::FunctionMismatch { .. })
         );
-        let mut rl_part = None;
-        let mut rr_part = None;
-        split(&mut r_part, 1, &mut rl_part, &mut rr_part, &self.functions);
+        assert_match!(
+            e.reverse_segment(3, 9).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+    }
 
-        merge(&mut self.root, &mut l_part, &mut rr_part, &self.functions);
+    #[test]
+    fn test_reverse_with_actions() {
+        let mut e = Exray::<i64, i64, Change>::new(vec![5, 1, 4, 2, 8, 3], vec![add(), min()]);
+        assert_match!(e.update_segment(0, 2, Change::Add(1)).err(), None);
+        assert_match!(e.reverse_segment(1, 4).err(), None);
+        assert_match!(e.update_segment(3, 5, Change::Assign(0)).err(), None);
+        assert_match!(e.reverse_segment(0, 3).err(), None);
+        assert_eq!(lazy_exray_to_vec(&mut e), vec![0, 2, 8, 6, 0, 0]);
+        assert_eq!(e.functions_values(), vec![16, 0]);
+    }
 
-        return Ok(());
+    fn sum_mod(m: i64) -> Func<i64, i64> {
+        return Rc::new(FnAggregate::new(
+            &format!("sum_mod_{}", m),
+            move |y: &i64| -> i64 { *y % m },
+            move |x: (&i64, u64), z: (&i64, u64)| -> i64 { (*x.0 + *z.0) % m },
+            || -> i64 { 0 },
+        ));
     }
+    #[test]
+    fn test_captured_parameter() {
+        let mut e = Exray::<i64, i64>::new(vec![3, 4, 5, 6], vec![sum_mod(7), sum_mod(5)]);
+        assert_eq!(e.functions_values(), vec![4, 3]);
+        assert_eq!(e.functions()[0].id(), "sum_mod_7");
 
-    /// erases whole segment [beg_ind; end_ind]
-    pub fn erase_segment(&mut self, beg_ind: usize, end_ind: usize) -> Result<(), ExrayError> {
-        if end_ind < beg_ind {
-            return Err(ExrayError::IndexError(String::from(
-                "End index is smaller than begin index!",
-            )));
-        }
-        if self.len() <= end_ind {
-            return Err(ExrayError::IndexError(String::from(
-                "End index is greater than last index!",
-            )));
-        }
+        let mut same = Exray::<i64, i64>::new(vec![2], vec![sum_mod(7), sum_mod(5)]);
+        assert_match!(e.insert_exray(&mut same, 4).err(), None);
+        assert_eq!(e.functions_values(), vec![6, 0]);
 
-        let mut l_part = None;
-        let mut r_part = None;
-        split(
-            &mut self.root,
-            beg_ind as u64,
-            &mut l_part,
-            &mut r_part,
-            &self.functions,
+        let mut swapped = Exray::<i64, i64>::new(vec![2], vec![sum_mod(5), sum_mod(7)]);
+        assert_match!(
+            e.insert_exray(&mut swapped, 0).err(),
+            Some(ExrayError::FunctionMismatch { .. })
         );
-        let mut rl_part = None;
-        let mut rr_part = None;
-        split(
-            &mut r_part,
-            (end_ind as u64) - (beg_ind as u64) + 1,
-            &mut rl_part,
-            &mut rr_part,
-            &self.functions,
+    }
+
+    struct Row {
+        cases: u64,
+        deaths: u64,
+    }
+    fn make_rows(v: &[(u64, u64)]) -> Vec<Row> {
+        return v
+            .iter()
+            .map(|&(cases, deaths)| Row {
+                cases: cases,
+                deaths: deaths,
+            })
+            .collect();
+    }
+    #[test]
+    fn test_typed_aggregates() {
+        let rows = make_rows(&[(3, 1), (5, 0), (2, 4), (6, 2)]);
+        let mut e: Exray<Row, (u64, u64, f64), NoAction, _> = Exray::with_aggregate(
+            rows,
+            (
+                Sum::by("cases", |r: &Row| r.cases),
+                Max::by("deaths", |r: &Row| r.deaths),
+                Avg::by("cases", |r: &Row| r.cases as f64),
+            ),
         );
+        assert_eq!(e.aggregate_value().unwrap(), (16, 4, 4.));
+        assert_eq!(e.functions()[0].id(), "(sum(cases),max(deaths),avg(cases))");
 
-        merge(&mut self.root, &mut l_part, &mut rr_part, &self.functions);
+        let r = e.segment_aggregate_value(1, 2);
+        assert_match!(r.as_ref().err(), None);
+        assert_eq!(r.unwrap(), (7, 4, 3.5));
+
+        assert_match!(e.erase_segment(2, 3).err(), None);
+        assert_eq!(e.aggregate_value().unwrap(), (8, 1, 4.));
+        assert_match!(e.erase_segment(0, 1).err(), None);
+        assert_eq!(e.aggregate_value().unwrap(), (0, 0, 0.));
+
+        let mut m = Exray::<i64, i64, NoAction, _>::with_aggregate(vec![], Max::new());
+        assert_eq!(m.aggregate_value().unwrap(), i64::MIN);
+        assert_match!(m.insert(0, -5).err(), None);
+        assert_match!(m.insert(0, -7).err(), None);
+        assert_eq!(m.aggregate_value().unwrap(), -5);
+    }
 
-        return Ok(());
+    #[test]
+    fn test_persistent() {
+        let mut v1 = PersistentExray::<i64, i64>::new(vec![1, 2, 3, 4, 5], vec![add(), first()]);
+        let mut v2 = v1.clone();
+        assert_match!(v2.insert(0, 10).err(), None);
+        assert_match!(v2.erase(3).err(), None);
+        assert_match!(v2.reverse_segment(1, 4).err(), None);
+        assert_eq!(v2.to_vec(), vec![&10, &5, &4, &2, &1]);
+        assert_eq!(v2.functions_values(), vec![22, 10]);
+        assert_eq!(v1.to_vec(), vec![&1, &2, &3, &4, &5]);
+        assert_eq!(v1.functions_values(), vec![15, 1]);
+
+        let mut v3 = v2.clone_segment(1, 3).unwrap();
+        *v3.get_mut(0).unwrap() = 7;
+        assert_eq!(v3.to_vec(), vec![&7, &4, &2]);
+        assert_eq!(v3.functions_values(), vec![13, 7]);
+        assert_eq!(v2.to_vec(), vec![&10, &5, &4, &2, &1]);
+
+        let v4 = v1.clone();
+        assert_match!(v1.insert_exray(&mut v3, 5).err(), None);
+        assert_eq!(v1.to_vec(), vec![&1, &2, &3, &4, &5, &7, &4, &2]);
+        assert_eq!(v1.functions_values(), vec![28, 1]);
+        assert_eq!(v4.to_vec(), vec![&1, &2, &3, &4, &5]);
+        assert_eq!(v2.functions_values(), vec![22, 10]);
+
+        let mut l1 = PersistentExray::<i64, i64, Change>::new(vec![5, 1, 4], vec![add(), min()]);
+        let mut l2 = l1.clone();
+        assert_match!(l2.update_segment(0, 1, Change::Add(3)).err(), None);
+        assert_eq!(l2.functions_values(), vec![16, 4]);
+        assert_eq!(*l2.get_element(1).unwrap(), 4);
+        assert_eq!(*l1.get_element(1).unwrap(), 1);
+        assert_eq!(l1.functions_values(), vec![10, 1]);
     }
 
-    /// inserts exray source to self at ind in log(N) time, because of this after this function source points to empty exray 
-    pub fn insert_exray(&mut self, source: &mut Self, ind: usize) -> Result<(), ExrayError> {
-        if self.len() < ind {
-            return Err(ExrayError::IndexError(String::from(
-                "Index greater than size!",
-            )));
-        }
-        if self.functions.len() != source.functions.len() {
-            return Err(ExrayError::IncompatibleExrayError(String::from(
-                "Different number of functions!",
-            )));
-        }
-        let len = self.functions.len();
-        for i in 0..len {
-            if self.functions[i] as usize != source.functions[i] as usize {
-                // high chance the functions are different
-                return Err(ExrayError::IncompatibleExrayError(String::from(
-                    "Different functions!",
-                )));
+    #[test]
+    fn test_read_only_queries() {
+        let mut rng = StdRng::seed_from_u64(11);
+        let mut v = (0..40)
+            .map(|_| rng.gen_range(-50, 50))
+            .collect::<Vec<i64>>();
+        let mut e = Exray::<i64, i64, Change>::with_rng(v.clone(), vec![add(), min(), first()], 5);
+        for _ in 0..200 {
+            let beg = rng.gen_range(0, v.len());
+            let end = rng.gen_range(beg, v.len());
+            match rng.gen_range(0, 3) {
+                0 => {
+                    let d = rng.gen_range(-10, 10);
+                    assert_match!(e.update_segment(beg, end, Change::Add(d)).err(), None);
+                    v[beg..=end].iter_mut().for_each(|x| *x += d);
+                }
+                1 => {
+                    let x = rng.gen_range(-10, 10);
+                    assert_match!(e.update_segment(beg, end, Change::Assign(x)).err(), None);
+                    v[beg..=end].iter_mut().for_each(|y| *y = x);
+                }
+                _ => {
+                    assert_match!(e.reverse_segment(beg, end).err(), None);
+                    v[beg..=end].reverse();
+                }
             }
+
+            // the queries go through shared references, the pending tags and reversals stay in the treap
+            let (r1, r2) = (&e, &e);
+            let beg = rng.gen_range(0, v.len());
+            let end = rng.gen_range(beg, v.len());
+            let segment = &v[beg..=end];
+            let expected = vec![
+                segment.iter().sum::<i64>(),
+                *segment.iter().min().unwrap(),
+                segment[0],
+            ];
+            assert_eq!(r1.segment_functions_values(beg, end).unwrap(), expected);
+            assert_eq!(r2.segment_aggregate_value(beg, end).unwrap(), expected[0]);
+
+            let cloned = r1.clone_segment(beg, end).unwrap();
+            assert_match!(cloned.validate().err(), None);
+            assert_eq!(cloned.functions_values(), expected);
+            assert_eq!(cloned.into_iter().collect::<Vec<i64>>(), segment.to_vec());
         }
+        assert_match!(e.validate().err(), None);
+        assert_match!(
+            e.segment_functions_values(3, 40).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+    }
 
-        let mut l_part = None;
-        let mut r_part = None;
-        split(
-            &mut self.root,
-            ind as u64,
-            &mut l_part,
-            &mut r_part,
-            &self.functions,
+    #[test]
+    fn test_max_right_min_left() {
+        let e = Exray::<i64, i64>::new(vec![3, 1, 4, 1, 5, 9, 2, 6], vec![add(), min()]);
+        assert_eq!(e.max_right(0, |v| v[0] <= 9).unwrap(), 4);
+        assert_eq!(e.max_right(2, |v| v[0] <= 9).unwrap(), 4);
+        assert_eq!(e.max_right(0, |v| v[0] <= 100).unwrap(), 8);
+        assert_eq!(e.max_right(0, |v| v[0] < 3).unwrap(), 0);
+        assert_eq!(e.max_right(8, |_| false).unwrap(), 8);
+        assert_eq!(e.min_left(8, |v| v[0] <= 8).unwrap(), 6);
+        assert_eq!(e.min_left(5, |v| v[1] >= 1).unwrap(), 0);
+        assert_eq!(e.min_left(5, |v| v[1] >= 2).unwrap(), 4);
+        assert_eq!(e.min_left(0, |_| false).unwrap(), 0);
+        assert_match!(
+            e.max_right(9, |_| true).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+        assert_match!(
+            e.min_left(9, |_| true).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
         );
 
-        let mut temp = None;
-        merge(&mut temp, &mut l_part, &mut source.root, &self.functions);
-        merge(&mut self.root, &mut temp, &mut r_part, &self.functions);
+        // the boundaries are found with the pending tags and reversals in the treap
+        let mut rng = StdRng::seed_from_u64(3);
+        let mut v = (0..50).map(|_| rng.gen_range(0, 100)).collect::<Vec<i64>>();
+        let mut e = Exray::<i64, i64, Change>::new(v.clone(), vec![add(), min(), first()]);
+        for _ in 0..100 {
+            let beg = rng.gen_range(0, v.len());
+            let end = rng.gen_range(beg, v.len());
+            if rng.gen::<bool>() {
+                let d = rng.gen_range(-10, 10);
+                assert_match!(e.update_segment(beg, end, Change::Add(d)).err(), None);
+                v[beg..=end].iter_mut().for_each(|x| *x += d);
+            } else {
+                assert_match!(e.reverse_segment(beg, end).err(), None);
+                v[beg..=end].reverse();
+            }
 
-        *source = Self::new(vec![], vec![]);
-        return Ok(());
+            let x = rng.gen_range(0, 100);
+            let start = rng.gen_range(0, v.len() + 1);
+            let mut expected = start;
+            while expected < v.len() && v[start..=expected].iter().all(|&y| y >= x) {
+                expected += 1;
+            }
+            assert_eq!(
+                e.max_right(start, |values| values[1] >= x).unwrap(),
+                expected
+            );
+            let mut expected = start;
+            while expected > 0 && v[expected - 1..start].iter().all(|&y| y >= x) {
+                expected -= 1;
+            }
+            assert_eq!(
+                e.min_left(start, |values| values[1] >= x).unwrap(),
+                expected
+            );
+            // the first element of the segment is checked so the reversed values are used
+            let expected = v[..start].iter().rposition(|&y| y < x).map_or(0, |i| i + 1);
+            assert_eq!(
+                e.min_left(start, |values| values[2] >= x && values[1] >= x)
+                    .unwrap(),
+                expected
+            );
+        }
     }
 
-    /// makes new exray from segment [beg_ind; end_ind] in log(N) time
-    pub fn extract_segment(&mut self, beg_ind: usize, end_ind: usize) -> Result<Self, ExrayError> {
-        if end_ind < beg_ind {
-            return Err(ExrayError::IndexError(String::from(
-                "End index is smaller than begin index!",
-            )));
+    #[test]
+    fn test_sorted_exray() {
+        let mut s = SortedExray::<i64, i64>::new(vec![5, 1, 4, 1, 3, 9], vec![add(), min()]);
+        assert_eq!(s.to_vec(), vec![&1, &1, &3, &4, &5, &9]);
+        assert!(!s.is_empty());
+        assert_eq!(s.lower_bound(&1), 0);
+        assert_eq!(s.upper_bound(&1), 2);
+        assert_eq!(s.lower_bound(&6), 5);
+        assert_eq!(s.upper_bound(&10), 6);
+        assert_eq!(s.rank(&4), 3);
+        assert_eq!(s.select(3), Some(&4));
+        assert_eq!(s.select(6), None);
+
+        assert_eq!(s.insert(4), 4);
+        assert_eq!(s.insert(0), 0);
+        assert_eq!(s.to_vec(), vec![&0, &1, &1, &3, &4, &4, &5, &9]);
+        assert_eq!(s.functions_values(), &[27, 0]);
+        assert!(s.remove(&1));
+        assert!(!s.remove(&2));
+        assert_eq!(s.to_vec(), vec![&0, &1, &3, &4, &4, &5, &9]);
+
+        // the sum of the elements with keys in [3; 6)
+        assert_eq!(s.functions_values_between(&3, &6), vec![16, 3]);
+        assert_eq!(s.functions_values_between(&6, &9), vec![0, i64::MAX]);
+        assert_eq!(s.as_exray().max_right(0, |v| v[0] <= 8).unwrap(), 4);
+
+        let t = s.split_by_key(&4);
+        assert_eq!(s.to_vec(), vec![&0, &1, &3]);
+        assert_eq!(t.to_vec(), vec![&4, &4, &5, &9]);
+        assert_eq!(t.functions_values(), &[22, 4]);
+        assert_eq!(s.split_by_key(&7).len(), 0);
+
+        // the elements are sorted by the comparator and the equal ones stay in the order of insertion
+        let by_key: fn(&(i64, i64), &(i64, i64)) -> cmp::Ordering = |a, b| a.0.cmp(&b.0);
+        let mut p =
+            SortedExray::<(i64, i64), i64, Sum<i64, fn(&(i64, i64)) -> i64>>::with_comparator(
+                vec![(2, 0), (1, 1), (2, 2)],
+                vec![Sum::by("second", |x: &(i64, i64)| x.1)],
+                by_key,
+            );
+        p.insert((1, 3));
+        p.insert((2, 4));
+        assert_eq!(
+            p.iter().cloned().collect::<Vec<_>>(),
+            vec![(1, 1), (1, 3), (2, 0), (2, 2), (2, 4)]
+        );
+        assert_eq!(p.functions_values_between(&(2, 100), &(3, 0)), vec![6]);
+        assert_eq!(p.upper_bound(&(1, 100)), 2);
+    }
+
+    #[test]
+    fn test_element_guard() {
+        let mut e =
+            Exray::<i64, i64, Change>::new(vec![5, 1, 4, 2, 8, 3], vec![add(), min(), first()]);
+        assert_match!(e.update_segment(0, 3, Change::Add(10)).err(), None);
+        assert_match!(e.reverse_segment(1, 4).err(), None);
+        {
+            let mut element = e.get_mut(1).unwrap();
+            assert_eq!(*element, 8);
+            *element -= 10;
+            assert_eq!(*element, -2);
         }
-        if self.len() <= end_ind {
-            return Err(ExrayError::IndexError(String::from(
-                "End index greater than last index!",
-            )));
+        assert_eq!(e.functions_values(), vec![53, -2, 15]);
+        assert_eq!(e.segment_functions_values(1, 3).unwrap(), vec![24, -2, -2]);
+
+        assert_match!(e.update(0, |x| *x = 0).err(), None);
+        assert_eq!(e.functions_values(), vec![38, -2, 0]);
+        assert_match!(
+            e.update(6, |x| *x = 0).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+        assert!(e.get_mut(6).is_none());
+        assert_eq!(lazy_exray_to_vec(&mut e), vec![0, -2, 12, 14, 11, 3]);
+
+        // the shared nodes are copied before they are changed
+        let mut v1 = PersistentExray::<i64, i64>::new(vec![1, 2, 3], vec![add()]);
+        let v2 = v1.clone();
+        assert_match!(v1.update(2, |x| *x *= 10).err(), None);
+        assert_eq!(v1.functions_values(), vec![33]);
+        assert_eq!(v2.functions_values(), vec![6]);
+
+        // several changes through one guard follow the path that was pushed when it was made
+        let mut e = Exray::<i64, i64, Change>::new((0..100).collect(), vec![add(), min(), first()]);
+        assert_match!(e.update_segment(10, 60, Change::Add(1000)).err(), None);
+        assert_match!(e.reverse_segment(20, 79).err(), None);
+        {
+            let mut element = e.get_mut(50).unwrap();
+            *element += 1;
+            *element *= 2;
+            assert_eq!(*element, 2100);
         }
+        assert_match!(e.validate().err(), None);
+        assert_eq!(e.functions_values(), vec![4950 + 51000 + 1051, 0, 0]);
+    }
 
-        let mut l_part = None;
-        let mut r_part = None;
-        split(
-            &mut self.root,
-            beg_ind as u64,
-            &mut l_part,
-            &mut r_part,
-            &self.functions,
+    #[test]
+    fn test_sequence_api() {
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3, 4, 5, 6], vec![add(), first()]);
+        let mut tail = e.split_off(4).unwrap();
+        assert_eq!(exray_to_vec(&e), vec![1, 2, 3, 4]);
+        assert_eq!(exray_to_vec(&tail), vec![5, 6]);
+        assert_eq!(tail.functions_values(), vec![11, 5]);
+        assert_match!(
+            e.split_off(5).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
         );
-        let mut rl_part = None;
-        let mut rr_part = None;
-        split(
-            &mut r_part,
-            (end_ind as u64) - (beg_ind as u64) + 1,
-            &mut rl_part,
-            &mut rr_part,
-            &self.functions,
+        assert_eq!(e.split_off(4).unwrap().len(), 0);
+
+        assert_match!(tail.reverse_segment(0, 1).err(), None);
+        assert_match!(e.append(&mut tail).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![1, 2, 3, 4, 6, 5]);
+        assert_eq!(tail.len(), 0);
+        // the emptied exray keeps its functions
+        tail.push_back(7);
+        assert_eq!(tail.functions_values(), vec![7, 7]);
+
+        e.push_front(0);
+        e.push_back(8);
+        assert_eq!(e.first(), Some(&0));
+        assert_eq!(e.last(), Some(&8));
+        assert_eq!(e.functions_values(), vec![29, 0]);
+        assert_eq!(e.pop_front(), Some(0));
+        assert_eq!(e.pop_back(), Some(8));
+        assert_eq!(e.functions_values(), vec![21, 1]);
+
+        assert_match!(e.swap(0, 4).err(), None);
+        assert_match!(e.swap(5, 3).err(), None);
+        assert_match!(e.swap(2, 2).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![6, 2, 3, 5, 1, 4]);
+        assert_eq!(e.functions_values(), vec![21, 6]);
+        assert_match!(
+            e.swap(0, 6).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
         );
 
-        merge(&mut self.root, &mut l_part, &mut rr_part, &self.functions);
+        e.truncate(10);
+        assert_eq!(e.len(), 6);
+        e.truncate(2);
+        assert_eq!(exray_to_vec(&e), vec![6, 2]);
+        assert_eq!(e.functions_values(), vec![8, 6]);
+        e.clear();
+        assert_eq!(e.len(), 0);
+        assert!(e.is_empty());
+        assert_eq!(e.first(), None);
+        assert_eq!(e.pop_back(), None);
+        e.push_back(3);
+        assert_eq!(e.functions_values(), vec![3, 3]);
+
+        // the pending actions are applied to the popped elements
+        let mut l = Exray::<i64, i64, Change>::new(vec![5, 1, 4], vec![add(), min()]);
+        assert_match!(l.update_segment(0, 2, Change::Add(3)).err(), None);
+        assert_eq!(l.pop_back(), Some(7));
+        assert_eq!(l.pop_front(), Some(8));
+        assert_eq!(l.functions_values(), vec![4, 4]);
+    }
 
-        return Ok(Self {
-            root: rl_part,
-            functions: self.functions.clone(),
-        });
+    #[test]
+    fn test_splice() {
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3, 4, 5, 6], vec![add(), first()]);
+        let removed = e.splice(1..4, vec![10, 20]).unwrap();
+        assert_eq!(exray_to_vec(&e), vec![1, 10, 20, 5, 6]);
+        assert_eq!(e.functions_values(), vec![42, 1]);
+        assert_eq!(exray_to_vec(&removed), vec![2, 3, 4]);
+        assert_eq!(removed.functions_values(), vec![9, 2]);
+
+        // empty range only inserts and empty replacement only removes
+        assert_eq!(e.splice(5..5, 7..9).unwrap().len(), 0);
+        assert_eq!(exray_to_vec(&e), vec![1, 10, 20, 5, 6, 7, 8]);
+        let removed = e.splice(0..2, vec![]).unwrap();
+        assert_eq!(exray_to_vec(&removed), vec![1, 10]);
+        assert_eq!(e.functions_values(), vec![46, 20]);
+
+        let mut other = Exray::<i64, i64>::new(vec![30, 40], vec![add(), first()]);
+        assert_match!(other.reverse_segment(0, 1).err(), None);
+        let removed = e.splice_exray(1..3, &mut other).unwrap();
+        assert_eq!(exray_to_vec(&e), vec![20, 40, 30, 7, 8]);
+        assert_eq!(e.functions_values(), vec![105, 20]);
+        assert_eq!(exray_to_vec(&removed), vec![5, 6]);
+        assert_eq!(other.len(), 0);
+
+        let mut incompatible = Exray::<i64, i64>::new(vec![1], vec![add()]);
+        assert_match!(
+            e.splice_exray(0..1, &mut incompatible).err(),
+            Some(ExrayError::FunctionMismatch { .. })
+        );
+        assert_match!(
+            e.splice(Range { start: 3, end: 2 }, vec![1]).err(),
+            Some(ExrayError::InvalidRange { .. })
+        );
+        assert_match!(
+            e.splice(4..6, vec![1]).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+        assert_eq!(exray_to_vec(&e), vec![20, 40, 30, 7, 8]);
     }
 
-    /// clones segment [beg_ind; end_ind] into new exray
-    pub fn clone_segment(&mut self, beg_ind: usize, end_ind: usize) -> Result<Self, ExrayError>
-    where
-        T: Clone,
-        U: Clone,
-    {
-        if end_ind < beg_ind {
-            return Err(ExrayError::IndexError(String::from(
-                "End index is smaller than begin index!",
-            )));
-        }
-        if self.len() <= end_ind {
-            return Err(ExrayError::IndexError(String::from(
-                "End index greater than last index!",
-            )));
-        }
+    #[test]
+    fn test_move_and_rotate() {
+        let mut e = Exray::<i64, i64>::new((0..8).collect(), vec![add(), first()]);
+        assert_match!(e.move_segment(1, 3, 4).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![0, 4, 5, 6, 1, 2, 3, 7]);
+        assert_match!(e.move_segment(4, 6, 0).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![1, 2, 3, 0, 4, 5, 6, 7]);
+        assert_eq!(e.functions_values(), vec![28, 1]);
+        assert_match!(e.move_segment(0, 7, 0).err(), None);
+        assert_match!(
+            e.move_segment(0, 2, 6).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+        assert_match!(
+            e.move_segment(2, 8, 0).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
 
-        let mut l_part = None;
-        let mut r_part = None;
-        split(
-            &mut self.root,
-            beg_ind as u64,
-            &mut l_part,
-            &mut r_part,
-            &self.functions,
+        assert_match!(e.rotate_left(0..4, 3).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![0, 1, 2, 3, 4, 5, 6, 7]);
+        assert_match!(e.rotate_right(2..7, 2).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![0, 1, 5, 6, 2, 3, 4, 7]);
+        assert_match!(e.rotate_right(2..7, 0).err(), None);
+        assert_match!(e.rotate_left(2..7, 5).err(), None);
+        assert_match!(e.rotate_left(3..3, 0).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![0, 1, 5, 6, 2, 3, 4, 7]);
+        assert_match!(
+            e.rotate_left(2..7, 6).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
         );
-        let mut rl_part = None;
-        let mut rr_part = None;
-        split(
-            &mut r_part,
-            (end_ind as u64) - (beg_ind as u64) + 1,
-            &mut rl_part,
-            &mut rr_part,
-            &self.functions,
+        assert_match!(
+            e.rotate_right(2..7, 6).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+        assert_match!(
+            e.rotate_left(2..9, 1).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
         );
 
-        let new_root = clone_treap(&rl_part);
+        assert_match!(e.swap_segments(5..8, 0..2).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![3, 4, 7, 5, 6, 2, 0, 1]);
+        assert_eq!(e.functions_values(), vec![28, 3]);
+        assert_match!(e.swap_segments(0..0, 3..5).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![5, 6, 3, 4, 7, 2, 0, 1]);
+        // the empty segments inside the other segment or at its ends don't move it
+        assert_match!(e.swap_segments(4..4, 2..6).err(), None);
+        assert_match!(e.swap_segments(2..6, 2..2).err(), None);
+        assert_match!(e.swap_segments(6..6, 2..6).err(), None);
+        assert_match!(e.swap_segments(3..3, 3..3).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![5, 6, 3, 4, 7, 2, 0, 1]);
+        assert_match!(e.swap_segments(1..4, 8..8).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![5, 7, 2, 0, 1, 6, 3, 4]);
+        assert_eq!(e.functions_values(), vec![28, 5]);
+        assert_match!(
+            e.swap_segments(0..3, 2..4).err(),
+            Some(ExrayError::InvalidRange { .. })
+        );
+    }
 
-        merge(&mut r_part, &mut rl_part, &mut rr_part, &self.functions);
-        merge(&mut self.root, &mut l_part, &mut r_part, &self.functions);
+    #[test]
+    fn test_range_bounds() {
+        let mut e = Exray::<i64, i64>::new((0..10).collect(), vec![add(), first()]);
+        assert_eq!(e.range_functions_values(2..5).unwrap(), vec![9, 2]);
+        assert_eq!(e.range_functions_values(..=7).unwrap(), vec![28, 0]);
+        assert_eq!(e.range_functions_values(3..).unwrap(), vec![42, 3]);
+        assert_eq!(e.range_functions_values(..).unwrap(), vec![45, 0]);
+        assert_eq!(e.range_functions_values(4..4).unwrap(), vec![0, 0]);
+        assert_eq!(e.range_aggregate_value(8..).unwrap(), 17);
+        assert_match!(
+            e.range_functions_values(5..11).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+        assert_match!(
+            e.range_functions_values(5..=10).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
 
-        return Ok(Self {
-            root: new_root,
-            functions: self.functions.clone(),
-        });
+        // the empty ranges are no-ops
+        assert_match!(e.erase_range(1..1).err(), None);
+        assert_match!(e.reverse_range(10..).err(), None);
+        assert_eq!(e.extract_range(0..0).unwrap().len(), 0);
+        assert_eq!(e.clone_range(..0).unwrap().len(), 0);
+        assert_eq!(e.len(), 10);
+
+        assert_match!(e.erase_range(..2).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![2, 3, 4, 5, 6, 7, 8, 9]);
+        assert_match!(e.reverse_range(5..).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![2, 3, 4, 5, 6, 9, 8, 7]);
+        let extracted = e.extract_range(..=1).unwrap();
+        assert_eq!(exray_to_vec(&extracted), vec![2, 3]);
+        let cloned = e.clone_range(2..).unwrap();
+        assert_eq!(exray_to_vec(&cloned), vec![6, 9, 8, 7]);
+        assert_eq!(cloned.functions_values(), vec![30, 6]);
+        assert_eq!(
+            e.iter_range(..2).unwrap().cloned().collect::<Vec<_>>(),
+            vec![4, 5]
+        );
+        assert_match!(e.swap_segments(..1, 4..).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![8, 7, 5, 6, 9, 4]);
+        assert_match!(e.rotate_left(.., 2).err(), None);
+        assert_eq!(exray_to_vec(&e), vec![5, 6, 9, 4, 8, 7]);
+        assert_eq!(e.splice(1..=2, vec![0]).unwrap().len(), 2);
+        assert_eq!(exray_to_vec(&e), vec![5, 0, 4, 8, 7]);
+
+        let mut l = Exray::<i64, i64, Change>::new(vec![5, 1, 4], vec![add(), min()]);
+        assert_match!(l.update_range(1.., Change::Add(3)).err(), None);
+        assert_match!(l.update_range(1..1, Change::Assign(0)).err(), None);
+        assert_eq!(l.functions_values(), vec![16, 4]);
     }
 
-    /// returns functions values for the segment [beg_ind; end_ind] in Vector, needs the return type of functions to be cloneable
-    pub fn segment_functions_values(
-        &mut self,
-        beg_ind: usize,
-        end_ind: usize,
-    ) -> Result<Vec<U>, ExrayError>
-    where
-        U: Clone,
-    {
-        if end_ind < beg_ind {
-            return Err(ExrayError::IndexError(String::from(
-                "End index is smaller than begin index!",
-            )));
-        }
-        if self.len() <= end_ind {
-            return Err(ExrayError::IndexError(String::from(
-                "End index greater than last index!",
-            )));
-        }
+    #[test]
+    fn test_checked_access() {
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3, 4, 5], vec![add(), first()]);
+        assert_match!(e.reverse_segment(0, 2).err(), None);
+        assert_eq!(e.get(0), Some(&3));
+        assert_eq!(e.get(4), Some(&5));
+        assert_eq!(e.get(5), None);
+        assert!(e.get_mut(5).is_none());
+        *e.get_mut(4).unwrap() = 6;
+        assert_eq!(e.functions_values(), vec![16, 3]);
+
+        let view = e.get_segment_view(1..4).unwrap();
+        assert_eq!(view.len(), 3);
+        assert_eq!(view.to_vec(), vec![&2, &1, &4]);
+        assert_eq!(view[2], 4);
+        assert_eq!(view.get(3), None);
+        assert_eq!(view.functions_values(), vec![7, 2]);
+        assert_eq!(
+            (&view).into_iter().rev().cloned().collect::<Vec<_>>(),
+            vec![4, 1, 2]
+        );
+        assert!(e.get_segment_view(3..).unwrap().len() == 2);
+        assert!(e.get_segment_view(5..5).unwrap().is_empty());
+        assert!(e.get_segment_view(4..6).is_none());
+        assert!(e.get_segment_view(Range { start: 3, end: 2 }).is_none());
+
+        let empty = Exray::<i64, i64>::new(vec![], vec![add()]);
+        assert_eq!(empty.get(0), None);
+        assert_eq!(
+            empty.get_segment_view(..).unwrap().functions_values(),
+            vec![0]
+        );
+    }
 
-        let mut l_part = None;
+    #[test]
+    #[should_panic(expected = "index out of bounds: len is 3")]
+    fn test_index_out_of_bounds() {
+        let e = Exray::<i64, i64>::new(vec![1, 2, 3], vec![add()]);
+        let _ = e[3];
+    }
+
+    #[test]
+    fn test_error_values() {
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3], vec![add(), min()]);
+        assert_eq!(
+            e.erase(3).err(),
+            Some(ExrayError::IndexOutOfBounds { index: 3, len: 3 })
+        );
+        assert_eq!(
+            e.extract_segment(2, 1).err(),
+            Some(ExrayError::InvalidRange { beg: 2, end: 1 })
+        );
+        assert_eq!(
+            e.clone_range(1..=3).err(),
+            Some(ExrayError::IndexOutOfBounds { index: 4, len: 3 })
+        );
+        assert_eq!(
+            e.move_segment(0, 1, 2).err(),
+            Some(ExrayError::IndexOutOfBounds { index: 2, len: 1 })
+        );
+
+        let mut other = Exray::<i64, i64>::new(vec![4], vec![add(), first()]);
+        assert_eq!(
+            e.append(&mut other).err(),
+            Some(ExrayError::FunctionMismatch { position: 1 })
+        );
+        let mut other = Exray::<i64, i64>::new(vec![4], vec![add()]);
+        assert_eq!(
+            e.append(&mut other).err(),
+            Some(ExrayError::FunctionMismatch { position: 1 })
+        );
+
+        assert_eq!(
+            ExrayError::IndexOutOfBounds { index: 3, len: 3 }.to_string(),
+            "index 3 is out of bounds for length 3"
+        );
+        let boxed: Box<dyn Error> = Box::new(ExrayError::InvalidRange { beg: 2, end: 1 });
+        assert_eq!(boxed.to_string(), "invalid range from 2 to 1");
+    }
+
+    #[test]
+    fn test_function_registry() {
+        let mut registry = FunctionRegistry::new();
+        registry.register(add());
+        registry.register(min());
+        registry.register(first());
+        assert_eq!(registry.ids(), vec!["first", "min", "sum"]);
+        assert!(registry.contains("min"));
+        assert!(registry.get("max").is_none());
+        registry.register_as("add", add());
+        assert_eq!(registry.ids(), vec!["add", "first", "min", "sum"]);
+        assert_eq!(registry.get("add").unwrap().id(), "sum");
+
+        let functions = vec![
+            registry.get("first").unwrap().clone(),
+            registry.get("sum").unwrap().clone(),
+        ];
+        let mut e = Exray::<i64, i64>::new(vec![5, 2, 7], functions);
+        assert_eq!(e.function_ids(), ["first", "sum"]);
+        assert_eq!(e.function_position("sum"), Some(1));
+        assert_eq!(e.function_position("min"), None);
+
+        // the exrays are compatible when their functions have the same ids, even if they are different objects
+        let mut other = Exray::<i64, i64>::new(vec![1], vec![first(), add()]);
+        e.append(&mut other).unwrap();
+        assert_eq!(e.functions_values(), vec![5, 15]);
+        let mut part = e.extract_range(2..).unwrap();
+        assert_eq!(part.function_ids(), ["first", "sum"]);
+        let mut other = Exray::<i64, i64>::new(vec![1], vec![add(), first()]);
+        assert_eq!(
+            part.append(&mut other).err(),
+            Some(ExrayError::FunctionMismatch { position: 0 })
+        );
+    }
+
+    #[test]
+    fn test_add_remove_function() {
+        let mut e = Exray::<i64, i64, Change>::new(vec![5, 1, 4, 2, 8, 3], vec![add(), min()]);
+        e.update_range(0..3, Change::Add(1)).unwrap();
+        e.reverse_range(1..5).unwrap();
+        e.update_range(3.., Change::Assign(0)).unwrap();
+
+        // the new function is computed also for the subtrees with pending actions and reversals
+        e.add_function(first());
+        assert_eq!(e.function_ids(), ["sum", "min", "first"]);
+        assert_eq!(e.functions_values(), vec![16, 0, 6]);
+        assert_eq!(e.range_functions_values(1..3).unwrap(), vec![10, 2, 8]);
+        e.reverse_range(0..4).unwrap();
+        assert_eq!(e.functions_values(), vec![16, 0, 0]);
+        assert_eq!(e.range_functions_values(1..4).unwrap(), vec![16, 2, 2]);
+        e.update_range(1..3, Change::Add(5)).unwrap();
+        assert_eq!(e.range_functions_values(1..3).unwrap(), vec![20, 7, 7]);
+
+        assert_eq!(e.remove_function(1).unwrap().id(), "min");
+        assert_eq!(e.function_ids(), ["sum", "first"]);
+        assert_eq!(e.functions_values(), vec![26, 0]);
+        e.update_range(0..2, Change::Add(1)).unwrap();
+        assert_eq!(e.functions_values(), vec![28, 1]);
+        assert_eq!(e.range_functions_values(2..4).unwrap(), vec![19, 13]);
+        e.reverse_range(..).unwrap();
+        assert_eq!(e.functions_values(), vec![28, 0]);
+        assert_eq!(lazy_exray_to_vec(&mut e), vec![0, 0, 6, 13, 8, 1]);
+
+        assert_eq!(
+            e.remove_function(2).err().map(|e| e.to_string()),
+            Some(String::from("index 2 is out of bounds for length 2"))
+        );
+        assert!(e.remove_function_by_id("max").is_none());
+        assert!(e.remove_function_by_id("sum").is_some());
+        assert_eq!(e.function_ids(), ["first"]);
+        assert_eq!(e.functions_values(), vec![0]);
+        assert_eq!(e.range_functions_values(3..).unwrap(), vec![13]);
+        // without functions there is no aggregate value
+        assert!(e.remove_function(0).is_ok());
+        assert_eq!(e.aggregate_value(), Err(ExrayError::NoFunctions));
+        assert_eq!(
+            e.segment_aggregate_value(0, 2),
+            Err(ExrayError::NoFunctions)
+        );
+        assert_eq!(e.range_aggregate_value(1..3), Err(ExrayError::NoFunctions));
+
+        let mut e = ArenaExray::<i64, i64>::new(vec![3, 1, 2], vec![add()]);
+        e.add_function(min());
+        e.add_function(first());
+        assert_eq!(e.functions_values(), vec![6, 1, 3]);
+        e.reverse_range(0..2).unwrap();
+        assert_eq!(e.functions_values(), vec![6, 1, 1]);
+        e.remove_function(0).unwrap();
+        assert_eq!(e.functions_values(), vec![1, 1]);
+        e.remove_function(1).unwrap();
+        e.remove_function(0).unwrap();
+        assert!(e.functions_values().is_empty());
+        e.add_function(first());
+        assert_eq!(e.functions_values(), vec![1]);
+        assert_eq!(e.to_vec(), vec![&1, &3, &2]);
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut e =
+            Exray::<i64, i64, Change>::with_rng((1..=20).collect(), vec![add(), first()], 7);
+        assert_match!(e.validate().err(), None);
+        assert_match!(e.update_segment(3, 15, Change::Add(2)).err(), None);
+        assert_match!(e.reverse_segment(0, 10).err(), None);
+        assert_match!(e.update_segment(5, 8, Change::Assign(4)).err(), None);
+        assert_match!(e.reverse_segment(6, 19).err(), None);
+        assert_match!(e.erase(4).err(), None);
+        assert_match!(e.insert(11, 30).err(), None);
+        assert_match!(e.validate().err(), None);
+        let mut other = e.extract_segment(2, 9).unwrap();
+        assert_match!(other.validate().err(), None);
+        assert_match!(e.insert_exray(&mut other, 5).err(), None);
+        assert_match!(e.validate().err(), None);
+
+        let mut p = PersistentExray::<i64, i64>::new((1..=10).collect(), vec![add(), first()]);
+        let copy = p.clone();
+        assert_match!(p.reverse_segment(2, 7).err(), None);
+        assert_match!(p.validate().err(), None);
+        assert_match!(copy.validate().err(), None);
+        let mut a = ArenaExray::<i64, i64>::new((1..=10).collect(), vec![add(), min(), first()]);
+        assert_match!(a.reverse_segment(0, 6).err(), None);
+        assert_match!(a.validate().err(), None);
+
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3], vec![add(), first()]);
+        e.storage.get_mut(e.root.as_mut().unwrap()).value_all[0] += 1;
+        assert_match!(
+            e.validate().err(),
+            Some(InvariantError::Value { function: 0, .. })
+        );
+        e.storage.get_mut(e.root.as_mut().unwrap()).value_all[0] -= 1;
+        e.storage.get_mut(e.root.as_mut().unwrap()).value_all_rev[0] += 1;
+        assert_match!(
+            e.validate().err(),
+            Some(InvariantError::ReversedValue { function: 1, .. })
+        );
+        e.storage.get_mut(e.root.as_mut().unwrap()).value_all_rev[0] -= 1;
+        e.storage.get_mut(e.root.as_mut().unwrap()).cnt += 1;
+        assert_match!(
+            e.validate().err(),
+            Some(InvariantError::Count {
+                cnt: 4,
+                expected: 3,
+                ..
+            })
+        );
+        e.storage.get_mut(e.root.as_mut().unwrap()).cnt -= 1;
+        e.storage.get_mut(e.root.as_mut().unwrap()).y_key = i64::MIN;
+        assert_match!(e.validate().err(), Some(InvariantError::HeapOrder { .. }));
+        assert_eq!(
+            InvariantError::Count {
+                index: 1,
+                cnt: 4,
+                expected: 3
+            }
+            .to_string(),
+            "the node of element 1 has cnt 4 but its subtree has 3 elements"
+        );
+    }
+
+    #[test]
+    fn test_stats_and_dot() {
+        let e = Exray::<i64, i64>::new(Vec::<i64>::new(), vec![add()]);
+        assert_eq!(
+            e.stats(),
+            ExrayStats {
+                nodes: 0,
+                depth: 0,
+                bytes: 0
+            }
+        );
+        assert_eq!(e.to_dot(), "digraph exray {\n    node [shape=box];\n}\n");
+
+        let e = Exray::<i64, i64>::new((0..100).collect(), vec![add(), min()]);
+        let stats = e.stats();
+        assert_eq!(stats.nodes, 100);
+        assert!(7 <= stats.depth && stats.depth < 100);
+        assert!(
+            100 * mem::size_of::<ImplicitTreap<i64, i64, NoAction, BoxStorage>>() < stats.bytes
+        );
+        let a = ArenaExray::<i64, i64>::new((0..100).collect(), vec![add()]);
+        assert_eq!(
+            a.stats().bytes,
+            100 * mem::size_of::<ImplicitTreap<i64, i64, NoAction, ArenaStorage<i64, i64, NoAction>>>(
+            )
+        );
+
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3, 4], vec![add()]);
+        assert_match!(e.reverse_segment(0, 3).err(), None);
+        let dot = e.to_dot();
+        assert!(dot.starts_with("digraph exray {"));
+        assert_eq!(dot.matches(" -> ").count(), 3);
+        assert!(dot.contains("cnt: 4\\nvalues: [10]\\nrev"));
+        assert!(!dot.contains("reversed values"));
+
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3, 4], vec![add(), first()]);
+        assert_match!(e.reverse_segment(0, 3).err(), None);
+        let dot = e.to_dot();
+        assert!(dot.contains("cnt: 4\\nvalues: [10, 4]\\nreversed values: [1]\\nrev"));
+    }
+
+    #[test]
+    fn test_differential() {
+        // sum_mod is correct only for non-negative elements
+        let functions = vec![add(), min(), first(), sum_mod(7)];
+        let test = DifferentialTest::new(functions, |rng: &mut StdRng| rng.gen_range(0, 100))
+            .runs(5)
+            .operations(150);
+        assert_match!(test.run::<BoxStorage>().err(), None);
+        assert_match!(test.run::<RcStorage>().err(), None);
+        assert_match!(test.run::<ArenaStorage<i64, i64, NoAction>>().err(), None);
+
+        // subtraction is not associative, so the values depend on the shape of the treap
+        let sub: Func<i64, i64> = Rc::new(FnAggregate::new(
+            "sub",
+            |y: &i64| -> i64 { *y },
+            |x: (&i64, u64), z: (&i64, u64)| -> i64 { *x.0 - *z.0 },
+            || -> i64 { 0 },
+        ));
+        let test = DifferentialTest::new(vec![add(), sub], |rng: &mut StdRng| rng.gen_range(1, 10));
+        let failure = test.run::<BoxStorage>().unwrap_err();
+        assert!(failure.message.contains("functions values"));
+        assert!(failure.to_string().contains("initial: "));
+        // the minimized sequence fails, but it passes without any of its operations or initial elements
+        let check = |initial: &[i64], operations: &[Operation<i64>]| {
+            test.check::<BoxStorage>(failure.seed, initial, operations)
+        };
+        assert_match!(check(&failure.initial, &failure.operations).err(), Some(_));
+        for i in 0..failure.operations.len() {
+            let mut operations = failure.operations.clone();
+            operations.remove(i);
+            assert_match!(check(&failure.initial, &operations).err(), None);
+        }
+        for i in 0..failure.initial.len() {
+            let mut initial = failure.initial.clone();
+            initial.remove(i);
+            assert_match!(check(&initial, &failure.operations).err(), None);
+        }
+        assert_eq!(naive_values(&[9, 4, 3], &[add(), min()]), vec![16, 3]);
+
+        // the updates push lazy tags together with the reversals
+        let change = |rng: &mut StdRng| {
+            if rng.gen::<bool>() {
+                Change::Add(rng.gen_range(-10, 10))
+            } else {
+                Change::Assign(rng.gen_range(-10, 10))
+            }
+        };
+        let functions = vec![add(), min(), first()];
+        let test = DifferentialTest::new(functions, |rng: &mut StdRng| rng.gen_range(-50, 50))
+            .actions(change)
+            .runs(5)
+            .operations(150);
+        assert_match!(test.run::<BoxStorage>().err(), None);
+        assert_match!(test.run::<RcStorage>().err(), None);
+        assert_match!(test.run::<ArenaStorage<i64, i64, Change>>().err(), None);
+
+        // Change treats the first function as sum, so the tags break min in its place
+        let test =
+            DifferentialTest::new(vec![min(), add()], |rng: &mut StdRng| rng.gen_range(0, 9))
+                .actions(change);
+        let failure = test.run::<BoxStorage>().unwrap_err();
+        assert!(failure
+            .operations
+            .iter()
+            .any(|operation| matches!(operation, Operation::Update { .. })));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        use serde::de::DeserializeSeed;
+
+        let mut e = Exray::<i64, i64>::new(vec![3, 1, 4, 1, 5], vec![add(), first()]);
+        e.reverse_range(0..3).unwrap();
+        let text = serde_json::to_string(&e).unwrap();
+        assert_eq!(
+            text,
+            r#"{"functions":["sum","first"],"elements":[4,1,3,1,5]}"#
+        );
+
+        let mut registry = FunctionRegistry::new();
+        registry.register(add());
+        registry.register(min());
+        registry.register(first());
+        let read: Exray<i64, i64> = registry
+            .exray_seed()
+            .deserialize(&mut serde_json::Deserializer::from_str(&text))
+            .unwrap();
+        assert_eq!(exray_to_vec(&read), vec![4, 1, 3, 1, 5]);
+        assert_eq!(read.functions_values(), vec![14, 4]);
+        assert_eq!(read.range_functions_values(1..4).unwrap(), vec![5, 1]);
+        // the read exray maintains the same functions, so it is compatible with the original
+        e.append(&mut read.clone()).unwrap();
+        assert_eq!(e.len(), 10);
+
+        let text = r#"{"elements":[2,7],"functions":["min"]}"#;
+        let read: Exray<i64, i64> = registry
+            .exray_seed()
+            .deserialize(&mut serde_json::Deserializer::from_str(text))
+            .unwrap();
+        assert_eq!(read.functions_values(), vec![2]);
+        // the fields can be also in a sequence, like in the compact formats
+        let read: Exray<i64, i64> = registry
+            .exray_seed()
+            .deserialize(&mut serde_json::Deserializer::from_str(
+                r#"[["min"],[7,2]]"#,
+            ))
+            .unwrap();
+        assert_eq!(exray_to_vec(&read), vec![7, 2]);
+        assert_eq!(read.functions_values(), vec![2]);
+
+        let text = r#"{"functions":["max"],"elements":[2,7]}"#;
+        let result: Result<Exray<i64, i64>, _> = registry
+            .exray_seed()
+            .deserialize(&mut serde_json::Deserializer::from_str(text));
+        assert!(result.is_err());
+    }
+
+    // returns the y_keys of the nodes in order, they define the shape of the treap
+    fn y_keys(e: &Exray<i64, i64>) -> Vec<i64> {
+        let mut res = Vec::<i64>::new();
+        let mut stack = Vec::<&ImplicitTreap<i64, i64, NoAction, BoxStorage>>::new();
+        let mut sub = &e.root;
+        loop {
+            while sub.is_some() {
+                let node = e.storage.get(sub.as_ref().unwrap());
+                stack.push(node);
+                sub = &node.l;
+            }
+            match stack.pop() {
+                None => return res,
+                Some(node) => {
+                    res.push(node.y_key);
+                    sub = &node.r;
+                }
+            }
+        }
+    }
+    #[test]
+    fn test_seeded_rng() {
+        let mut e1 = Exray::<i64, i64>::with_rng(vec![1, 2, 3, 4, 5], vec![add()], 42);
+        let mut e2 = Exray::<i64, i64>::with_rng(vec![1, 2, 3, 4, 5], vec![add()], 42);
+        assert_eq!(y_keys(&e1), y_keys(&e2));
+        for e in [&mut e1, &mut e2].iter_mut() {
+            assert_match!(e.insert(2, 10).err(), None);
+            assert_match!(e.insert(0, 11).err(), None);
+            e.extend(vec![6, 7, 8]);
+        }
+        assert_eq!(y_keys(&e1), y_keys(&e2));
+
+        let mut s1 = e1.extract_segment(1, 3).unwrap();
+        let mut s2 = e2.extract_segment(1, 3).unwrap();
+        assert_match!(s1.insert(1, 12).err(), None);
+        assert_match!(s2.insert(1, 12).err(), None);
+        assert_eq!(y_keys(&s1), y_keys(&s2));
+        assert_match!(e1.insert_exray(&mut s1, 2).err(), None);
+        assert_match!(e2.insert_exray(&mut s2, 2).err(), None);
+        assert_eq!(y_keys(&e1), y_keys(&e2));
+        assert_eq!(e1.to_vec(), e2.to_vec());
+
+        // the clone doesn't repeat the y_keys of the original, but the clones of equal exrays are equal
+        let mut c1 = e1.clone();
+        let mut c2 = e2.clone();
+        for e in [&mut e1, &mut c1, &mut c2].iter_mut() {
+            e.extend(vec![9, 10, 11, 12]);
+        }
+        assert_ne!(y_keys(&e1), y_keys(&c1));
+        assert_eq!(y_keys(&c1), y_keys(&c2));
+        // every clone moves the rng of the exray, so two clones of the same exray get different y_keys
+        let mut d1 = e2.clone();
+        let mut d2 = e2.clone();
+        for e in [&mut d1, &mut d2].iter_mut() {
+            e.extend(vec![13, 14, 15, 16]);
+        }
+        assert_ne!(y_keys(&d1), y_keys(&d2));
+        assert_match!(d1.append(&mut d2).err(), None);
+        assert_match!(d1.validate().err(), None);
+
+        let e3 = Exray::<i64, i64>::with_rng(vec![1, 2, 3, 4, 5], vec![add()], 43);
+        assert_ne!(
+            y_keys(&e3),
+            y_keys(&Exray::with_rng(vec![1, 2, 3, 4, 5], vec![add()], 42))
+        );
+    }
+
+    #[test]
+    fn test_arena() {
+        let mut a = ArenaExray::<i64, i64>::new(vec![1, 2, 3, 4, 5], vec![add(), first()]);
+        let mut b = Exray::<i64, i64>::new(vec![1, 2, 3, 4, 5], vec![add(), first()]);
+        assert_match!(a.insert(2, 10).err(), None);
+        assert_match!(b.insert(2, 10).err(), None);
+        assert_match!(a.reverse_segment(1, 4).err(), None);
+        assert_match!(b.reverse_segment(1, 4).err(), None);
+        assert_eq!(a.to_vec(), b.to_vec());
+        assert_eq!(a.functions_values(), b.functions_values());
+
+        // the freed slots are used again
+        let capacity = a.storage.capacity();
+        assert_match!(a.erase_segment(0, 2).err(), None);
+        assert_match!(a.insert(0, 6).err(), None);
+        assert_match!(a.insert(0, 7).err(), None);
+        assert_eq!(a.storage.capacity(), capacity);
+        assert_eq!(a.to_vec(), vec![&7, &6, &10, &2, &5]);
+        assert_eq!(a.functions_values(), vec![30, 7]);
+
+        // the nodes are moved between the arenas
+        let mut c = a.extract_segment(1, 3).unwrap();
+        assert_eq!(c.to_vec(), vec![&6, &10, &2]);
+        assert_eq!(c.functions_values(), vec![18, 6]);
+        assert_eq!(a.to_vec(), vec![&7, &5]);
+        assert_match!(c.reverse_segment(0, 2).err(), None);
+        assert_match!(a.insert_exray(&mut c, 2).err(), None);
+        assert_eq!(a.to_vec(), vec![&7, &5, &2, &10, &6]);
+        assert_eq!(a.functions_values(), vec![30, 7]);
+        assert_eq!(c.len(), 0);
+
+        let d = a.clone();
+        assert_match!(a.erase(0).err(), None);
+        assert_eq!(d.to_vec(), vec![&7, &5, &2, &10, &6]);
+        assert_eq!(
+            a.iter().rev().cloned().collect::<Vec<_>>(),
+            vec![6, 10, 2, 5]
+        );
+
+        let mut l = ArenaExray::<i64, i64, Change>::new(vec![5, 1, 4], vec![add(), min()]);
+        assert_match!(l.update_segment(0, 1, Change::Add(3)).err(), None);
+        assert_eq!(l.functions_values(), vec![16, 4]);
+        assert_eq!(l.into_iter().collect::<Vec<_>>(), vec![8, 4, 4]);
+    }
+
+    #[test]
+    fn test_iterators() {
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3, 4, 5, 6, 7], vec![add(), first()]);
+        assert_match!(e.reverse_segment(1, 5).err(), None);
+        assert_eq!(e.iter().collect::<Vec<_>>(), e.to_vec());
+        assert_eq!(
+            e.iter().rev().cloned().collect::<Vec<_>>(),
+            vec![7, 2, 3, 4, 5, 6, 1]
+        );
+        assert_eq!(e.iter().len(), 7);
+
+        let mut it = e.iter();
+        assert_eq!(it.next(), Some(&1));
+        assert_eq!(it.next_back(), Some(&7));
+        assert_eq!(it.len(), 5);
+        assert_eq!(it.next_back(), Some(&2));
+        assert_eq!(it.next(), Some(&6));
+        assert_eq!(it.cloned().collect::<Vec<_>>(), vec![5, 4, 3]);
+
+        let r = e.iter_range(2..5);
+        assert_match!(r.as_ref().err(), None);
+        let range = r.unwrap();
+        assert_eq!(range.len(), 3);
+        assert_eq!(range.rev().cloned().collect::<Vec<_>>(), vec![3, 4, 5]);
+        assert_eq!(e.iter_range(3..3).unwrap().next(), None);
+        assert_match!(
+            e.iter_range(2..8).err(),
+            Some(ExrayError::IndexOutOfBounds { .. })
+        );
+
+        let mut sum = 0;
+        for x in &e {
+            sum += x;
+        }
+        assert_eq!(sum, 28);
+
+        e.iter_mut(|values| {
+            for x in values {
+                *x *= 2;
+            }
+        });
+        assert_eq!(exray_to_vec(&e), vec![2, 12, 10, 8, 6, 4, 14]);
+        assert_eq!(e.functions_values(), vec![56, 2]);
+        let changed = e.iter_mut(|mut values| {
+            assert_eq!(values.len(), 7);
+            *values.next_back().unwrap() = 0;
+            *values.next().unwrap() = 3;
+            return values.len();
+        });
+        assert_eq!(changed, 5);
+        assert_eq!(e.functions_values(), vec![43, 3]);
+        assert_eq!(e.segment_functions_values(0, 1).unwrap(), vec![15, 3]);
+
+        let mut l = Exray::<i64, i64, Change>::new(vec![5, 1, 4, 2, 8, 3], vec![add(), min()]);
+        assert_match!(l.update_segment(1, 4, Change::Add(1)).err(), None);
+        assert_match!(l.reverse_segment(0, 3).err(), None);
+        l.iter_mut(|values| values.for_each(|x| *x -= 1));
+        assert_eq!(l.functions_values(), vec![21, 1]);
+        let mut it = l.into_iter();
+        assert_eq!(it.len(), 6);
+        assert_eq!(it.next_back(), Some(2));
+        assert_eq!(it.collect::<Vec<_>>(), vec![2, 4, 1, 4, 8]);
+
+        let p1 = PersistentExray::<i64, i64>::new(vec![1, 2, 3], vec![add()]);
+        let mut p2 = p1.clone();
+        p2.iter_mut(|values| values.for_each(|x| *x += 10));
+        assert_eq!(p2.functions_values(), vec![36]);
+        assert_eq!(p1.functions_values(), vec![6]);
+        assert_eq!(p2.into_iter().collect::<Vec<_>>(), vec![11, 12, 13]);
+        assert_eq!(p1.into_iter().rev().collect::<Vec<_>>(), vec![3, 2, 1]);
+
+        // the function values are recovered also when the changing function panics
+        let mut e = Exray::<i64, i64>::new(vec![1, 2, 3], vec![add()]);
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            e.iter_mut(|values| {
+                for x in values {
+                    *x += 1;
+                    if *x == 3 {
+                        panic!("stopped changing");
+                    }
+                }
+            })
+        }));
+        assert!(result.is_err());
+        assert_match!(e.validate().err(), None);
+        assert_eq!(e.functions_values(), vec![8]);
+    }
+
+    #[test]
+    fn test_bulk_construction() {
+        let n = 10000;
+        let mut e = Exray::<i64, i64>::new((0..n).collect(), vec![add(), min(), first()]);
+        assert_eq!(e.len(), n as usize);
+        assert_eq!(e.functions_values(), vec![n * (n - 1) / 2, 0, 0]);
+        assert_eq!(
+            e.segment_functions_values(100, 199).unwrap(),
+            vec![14950, 100, 100]
+        );
+        assert_match!(e.reverse_segment(0, 9).err(), None);
+        assert_eq!(e.functions_values(), vec![n * (n - 1) / 2, 0, 9]);
+
+        e.extend(vec![-1, -2, -3]);
+        assert_eq!(e.len(), n as usize + 3);
+        assert_eq!(e.functions_values(), vec![n * (n - 1) / 2 - 6, -3, 9]);
+        assert_eq!(
+            e.iter().rev().take(4).cloned().collect::<Vec<_>>(),
+            vec![-3, -2, -1, n - 1]
+        );
+
+        let collected: Exray<i64, i64> = (1..=5).collect();
+        assert_eq!(collected.to_vec(), vec![&1, &2, &3, &4, &5]);
+        assert_eq!(collected.functions().len(), 0);
+        let empty: Exray<i64, i64> = vec![].into_iter().collect();
+        assert_eq!(empty.len(), 0);
+    }
+
+    #[test]
+    fn test_deep_treap() {
+        let child = std::thread::Builder::new()
+            .stack_size(256 * 1024)
+            .spawn(|| {
+                let n = 200000;
+                let mut e = Exray::<i64, i64>::new(vec![], vec![add()]);
+                // the y_keys are increasing, so every new node becomes root and the treap is a path with depth n
+                for i in 0..n {
+                    let mut leaf = make_leaf(
+                        i,
+                        &e.functions,
+                        &e.rev_functions,
+                        e.rng.get_mut(),
+                        &mut e.storage,
+                    );
+                    e.storage.get_mut(leaf.as_mut().unwrap()).y_key = i;
+                    let mut temp = None;
+                    merge(
+                        &mut temp,
+                        &mut e.root,
+                        &mut leaf,
+                        &e.functions,
+                        &e.rev_functions,
+                        &mut e.storage,
+                    );
+                    e.root = temp;
+                }
+                assert_eq!(e.functions_values(), vec![n * (n - 1) / 2]);
+                assert_eq!(e[0], 0);
+                assert_eq!(*e.get_element(1).unwrap(), 1);
+                assert_eq!(e.to_vec().len(), n as usize);
+                assert_eq!(e.iter().rev().count(), n as usize);
+
+                let cloned = e.clone();
+                assert_match!(e.insert(0, -1).err(), None);
+                assert_match!(e.erase_segment(1, (n - 10) as usize).err(), None);
+                let mut expected = vec![-1];
+                expected.extend(n - 10..n);
+                assert_eq!(exray_to_vec(&e), expected);
+                assert_eq!(cloned.functions_values(), vec![n * (n - 1) / 2]);
+
+                let mut other = cloned.clone();
+                other.iter_mut(|values| values.for_each(|x| *x = 1));
+                assert_eq!(other.functions_values(), vec![n]);
+                assert_match!(other.reverse_segment(0, (n - 1) as usize).err(), None);
+                assert_eq!(other.segment_functions_values(5, 14).unwrap(), vec![10]);
+            })
+            .unwrap();
+        child.join().unwrap();
+    }
+
+    /// runs random workload of inserts, erases, queries and reversals and returns checksum of the results, so the storages can be compared on the same workload
+    fn run_workload<S: Storage<i64, (i64, i64), NoAction>>(n: usize, seed: u64) -> i64 {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let elements = (0..n as i64).collect::<Vec<_>>();
+        let mut e = Exray::<i64, (i64, i64), NoAction, (Sum<i64>, Max<i64>), S>::with_aggregate(
+            elements,
+            (Sum::new(), Max::new()),
+        );
+        let mut checksum = 0;
+        for i in 0..n {
+            let len = e.len();
+            let ind = rng.gen_range(0, len);
+            e.insert(ind, i as i64).unwrap();
+            e.erase(rng.gen_range(0, len + 1)).unwrap();
+            let beg = rng.gen_range(0, len);
+            let end = rng.gen_range(beg, len);
+            checksum += e.segment_aggregate_value(beg, end).unwrap().0;
+            if i % 64 == 0 {
+                e.reverse_segment(beg, end).unwrap();
+            }
+        }
+        for x in &e {
+            checksum ^= *x;
+        }
+        return checksum;
+    }
+    #[test]
+    fn test_storages_workload() {
+        let n = 2_000;
+        let box_sum = run_workload::<BoxStorage>(n, 7);
+        let rc_sum = run_workload::<RcStorage>(n, 7);
+        let arena_sum = run_workload::<ArenaStorage<i64, (i64, i64), NoAction>>(n, 7);
+        assert_eq!(box_sum, rc_sum);
+        assert_eq!(box_sum, arena_sum);
+    }
+}
+
+/// Exray name comes from the beginning and ending of extended-array :)
+/// the type A is the action that can be applied over whole segments with update_segment, by default there is no such action
+/// the type F is the aggregate for the maintained functions, by default they are shared trait objects so exrays with different functions have the same type
+/// the type S is the storage of the nodes, with the default BoxStorage every node is in its own Box and with RcStorage the exray is persistent
+pub struct Exray<T, U, A = NoAction, F = Func<T, U>, S: Storage<T, U, A> = BoxStorage> {
+    /// link to the root of the implicit treap
+    root: Link<T, U, A, S>,
+    /// Vector containing the functions that are maintained by the treap
+    functions: Vec<F>,
+    /// the ids of the functions, they are built once so the checks for compatibility and the lookups by name don't call id every time
+    ids: Vec<String>,
+    /// indices of the non-commutative functions, for them the treap maintains also the values for the reversed segments
+    rev_functions: Vec<usize>,
+    /// the storage in which the nodes of the treap are
+    storage: S,
+    /// the generator of the y_keys, the exrays made with the same seed and changed in the same way have the same treaps
+    /// it is in RefCell only for clone, which takes the seed of the copy from it through &self, the other methods use get_mut
+    rng: RefCell<StdRng>,
+}
+
+/// PersistentExray shares the nodes between its copies, so clone is O(1) and every change of some copy makes new version in O(log(N)) time and memory without changing the other copies
+pub type PersistentExray<T, U, A = NoAction, F = Func<T, U>> = Exray<T, U, A, F, RcStorage>;
+
+/// ArenaExray keeps all of its nodes in one Vector with the values of the functions inline, which is faster for big exrays but it has no iter_mut
+/// the nodes moved between two arena exrays are copied, so insert_exray, split_off, extract and splice_exray take O(M + log(N)) time for M moved elements
+/// only one value is inline - with two or more functions value_all is a Vector on the heap like with BoxStorage, and so is value_all_rev with two or more non-commutative functions, so the tuple aggregates of with_aggregate are the case that stays inline
+/// the times of the storages on the same workload are measured with: cargo bench --bench storages
+pub type ArenaExray<T, U, A = NoAction, F = Func<T, U>> = Exray<T, U, A, F, ArenaStorage<T, U, A>>;
+
+/// the part before the place of the split and the rest, as split_link returns them
+type SplitLinks<T, U, A, S> = (Link<T, U, A, S>, Link<T, U, A, S>);
+
+/// the errors of the exray operations, they keep the values which made the operation fail
+#[derive(Debug, PartialEq)]
+pub enum ExrayError {
+    /// index is outside of the exray or the part of it which is used, len is the size of that part
+    IndexOutOfBounds { index: usize, len: usize },
+    /// the range from beg to end can't be used, its end is before its begin or it overlaps another range of the operation
+    InvalidRange { beg: usize, end: usize },
+    /// the other exray maintains different functions, position is the index of the first function which differs
+    FunctionMismatch { position: usize },
+    /// the exray doesn't maintain any functions, so there is no aggregate value
+    NoFunctions,
+}
+
+impl fmt::Display for ExrayError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ExrayError::IndexOutOfBounds { index, len } => {
+                write!(f, "index {} is out of bounds for length {}", index, len)
+            }
+            ExrayError::InvalidRange { beg, end } => {
+                write!(f, "invalid range from {} to {}", beg, end)
+            }
+            ExrayError::FunctionMismatch { position } => {
+                write!(
+                    f,
+                    "the exrays have different functions at position {}",
+                    position
+                )
+            }
+            ExrayError::NoFunctions => write!(f, "the exray doesn't maintain any functions"),
+        }
+    }
+}
+
+impl Error for ExrayError {}
+
+/// turns range into [start; end) for exray with len elements, the range can be empty but it should be inside the exray
+fn resolve_range<R: RangeBounds<usize>>(range: R, len: usize) -> Result<Range<usize>, ExrayError> {
+    let start = match range.start_bound() {
+        Bound::Included(&start) => start,
+        Bound::Excluded(&start) => start.saturating_add(1),
+        Bound::Unbounded => 0,
+    };
+    let end = match range.end_bound() {
+        Bound::Included(&end) => end.saturating_add(1),
+        Bound::Excluded(&end) => end,
+        Bound::Unbounded => len,
+    };
+    if end < start {
+        return Err(ExrayError::InvalidRange {
+            beg: start,
+            end: end,
+        });
+    }
+    if len < end {
+        return Err(ExrayError::IndexOutOfBounds {
+            index: end,
+            len: len,
+        });
+    }
+    return Ok(start..end);
+}
+
+impl<T, U, A: Action<T, U>, F: Aggregate<T, Value = U>, S: Storage<T, U, A>> Exray<T, U, A, F, S> {
+    /// function for making exray with values from the Vector elements and maintaining the functions in Vector functions
+    /// the treap is built in O(N) time
+    pub fn new(elements: Vec<T>, functions: Vec<F>) -> Self {
+        return Self::with_rng(elements, functions, rand::thread_rng().gen::<u64>());
+    }
+
+    /// like new, but the y_keys are generated from seed, so the shape of the treap is the same in every run for the same operations
+    pub fn with_rng(elements: Vec<T>, functions: Vec<F>, seed: u64) -> Self {
+        let mut rev_functions = Vec::<usize>::new();
+        let mut ids = Vec::<String>::new();
+        for (i, function) in functions.iter().enumerate() {
+            if !function.is_commutative() {
+                rev_functions.push(i);
+            }
+            ids.push(function.id().into_owned());
+        }
+        let mut exray = Exray::<T, U, A, F, S> {
+            root: None,
+            functions: functions,
+            ids: ids,
+            rev_functions: rev_functions,
+            storage: S::default(),
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
+        };
+        exray.root = build_treap(
+            elements,
+            &exray.functions,
+            &exray.rev_functions,
+            exray.rng.get_mut(),
+            &mut exray.storage,
+        );
+        return exray;
+    }
+
+    /// function for making exray maintaining only aggregate, which is usually a tuple of aggregates with different value types like (Sum<u64>, Max<u64>, Avg)
+    pub fn with_aggregate(elements: Vec<T>, aggregate: F) -> Self {
+        return Self::new(elements, vec![aggregate]);
+    }
+
+    pub fn len(&self) -> usize {
+        get_cnt(&self.root, &self.storage) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.root.is_none()
+    }
+
+    pub fn functions(&self) -> &[F] {
+        &self.functions
+    }
+
+    /// returns the ids of the functions in the order of the functions
+    pub fn function_ids(&self) -> &[String] {
+        &self.ids
+    }
+
+    /// returns the index of the function with id, its value is at this index in the functions values
+    pub fn function_position(&self, id: &str) -> Option<usize> {
+        self.ids.iter().position(|function_id| function_id == id)
+    }
+
+    /// starts maintaining function, its values are computed for all nodes in one O(N) pass and they are after the values of the other functions
+    /// Action::apply_fvalue gets the index of the function, so the actions applied after this should know the new function by the index functions().len() - 1
+    /// the pending actions are pushed to the elements before the values are computed, so the actions applied before this are never called with the new index
+    pub fn add_function(&mut self, function: F) {
+        let ind = self.functions.len();
+        add_function_values(
+            &mut self.root,
+            &function,
+            ind,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+        if !function.is_commutative() {
+            self.rev_functions.push(ind);
+        }
+        self.ids.push(function.id().into_owned());
+        self.functions.push(function);
+    }
+
+    /// stops maintaining the function with index ind and returns it, its values are dropped from all nodes in one O(N) pass
+    /// Action::apply_fvalue gets the index of the function and the next functions move one index back, so the actions applied after this should know them by their new indices
+    /// the pending actions are pushed to the elements before the values are dropped, so the actions applied before this are never called with the new indices
+    pub fn remove_function(&mut self, ind: usize) -> Result<F, ExrayError> {
+        if self.functions.len() <= ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: ind,
+                len: self.functions.len(),
+            });
+        }
+        // the pending tags are pushed with the old indices of the functions before their values are dropped
+        remove_function_values(&mut self.root, ind, &self.rev_functions, &mut self.storage);
+        self.rev_functions.retain(|&i| i != ind);
+        for i in self.rev_functions.iter_mut() {
+            if ind < *i {
+                *i -= 1;
+            }
+        }
+        self.ids.remove(ind);
+        return Ok(self.functions.remove(ind));
+    }
+
+    /// like remove_function, but the function is found by its id, None is returned if the exray doesn't maintain it
+    /// the indices of the next functions change like in remove_function
+    pub fn remove_function_by_id(&mut self, id: &str) -> Option<F> {
+        return match self.function_position(id) {
+            None => None,
+            Some(ind) => self.remove_function(ind).ok(),
+        };
+    }
+
+    /// makes a treap with one node for value, with the values of all functions for it
+    fn make_node(&mut self, value: T) -> Link<T, U, A, S> {
+        return make_leaf(
+            value,
+            &self.functions,
+            &self.rev_functions,
+            self.rng.get_mut(),
+            &mut self.storage,
+        );
+    }
+
+    /// inserts value at ind in exray
+    pub fn insert(&mut self, ind: usize, value: T) -> Result<(), ExrayError> {
+        if self.len() < ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: ind,
+                len: self.len(),
+            });
+        }
+
+        let mut l_part = None;
+        let mut r_part = None;
+        split(
+            &mut self.root,
+            ind as u64,
+            &mut l_part,
+            &mut r_part,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+
+        let mut new_treap = self.make_node(value);
+        let mut temp = None;
+        merge(
+            &mut temp,
+            &mut l_part,
+            &mut new_treap,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+        merge(
+            &mut self.root,
+            &mut temp,
+            &mut r_part,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+
+        return Ok(());
+    }
+
+    /// erases the element at ind
+    pub fn erase(&mut self, ind: usize) -> Result<(), ExrayError> {
+        if self.len() <= ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: ind,
+                len: self.len(),
+            });
+        }
+
+        let mut l_part = None;
+        let mut r_part = None;
+        split(
+            &mut self.root,
+            ind as u64,
+            &mut l_part,
+            &mut r_part,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+        let mut rl_part = None;
+        let mut rr_part = None;
+        split(
+            &mut r_part,
+            1,
+            &mut rl_part,
+            &mut rr_part,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+
+        merge(
+            &mut self.root,
+            &mut l_part,
+            &mut rr_part,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+        drop_treap(&mut rl_part, &mut self.storage);
+
+        return Ok(());
+    }
+
+    /// erases whole segment [beg_ind; end_ind]
+    pub fn erase_segment(&mut self, beg_ind: usize, end_ind: usize) -> Result<(), ExrayError> {
+        if end_ind < beg_ind {
+            return Err(ExrayError::InvalidRange {
+                beg: beg_ind,
+                end: end_ind,
+            });
+        }
+        if self.len() <= end_ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: end_ind,
+                len: self.len(),
+            });
+        }
+        return self.erase_range(beg_ind..=end_ind);
+    }
+
+    /// erases the elements in range, for empty range nothing is changed
+    pub fn erase_range<R: RangeBounds<usize>>(&mut self, range: R) -> Result<(), ExrayError> {
+        let range = resolve_range(range, self.len())?;
+        let root = self.root.take();
+        let (l_part, r_part) = self.split_link(root, range.start);
+        let (mut removed, rr_part) = self.split_link(r_part, range.end - range.start);
+        self.root = self.merge_links(l_part, rr_part);
+        drop_treap(&mut removed, &mut self.storage);
+        return Ok(());
+    }
+
+    /// inserts exray source to self at ind in log(N) time, because of this after this function source is empty exray with the same functions
+    /// for ArenaExray the nodes of source are copied to the arena of self, so it is O(M + log(N)) for M elements of source
+    pub fn insert_exray(&mut self, source: &mut Self, ind: usize) -> Result<(), ExrayError> {
+        if self.len() < ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: ind,
+                len: self.len(),
+            });
+        }
+        self.check_compatible(source)?;
+
+        let mut l_part = None;
         let mut r_part = None;
         split(
             &mut self.root,
-            beg_ind as u64,
+            ind as u64,
+            &mut l_part,
+            &mut r_part,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+
+        let mut source_root = self
+            .storage
+            .move_tree(source.root.take(), &mut source.storage);
+        let mut temp = None;
+        merge(
+            &mut temp,
+            &mut l_part,
+            &mut source_root,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+        merge(
+            &mut self.root,
+            &mut temp,
+            &mut r_part,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+
+        // source keeps its functions and its rng, so it can be used again like an empty Vec after append
+        source.storage = S::default();
+        return Ok(());
+    }
+
+    /// splits the treap curr at ind and returns the part before ind and the rest
+    fn split_link(&mut self, mut curr: Link<T, U, A, S>, ind: usize) -> SplitLinks<T, U, A, S> {
+        let mut l_part = None;
+        let mut r_part = None;
+        split(
+            &mut curr,
+            ind as u64,
             &mut l_part,
             &mut r_part,
             &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+        return (l_part, r_part);
+    }
+
+    /// merges the treaps l_part and r_part, in this order
+    fn merge_links(
+        &mut self,
+        mut l_part: Link<T, U, A, S>,
+        mut r_part: Link<T, U, A, S>,
+    ) -> Link<T, U, A, S> {
+        let mut curr = None;
+        merge(
+            &mut curr,
+            &mut l_part,
+            &mut r_part,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+        return curr;
+    }
+
+    /// takes the element at ind out of the exray, ind should be smaller than the size
+    fn take_element(&mut self, ind: usize) -> T {
+        let root = self.root.take();
+        let (l_part, r_part) = self.split_link(root, ind);
+        let (element, rr_part) = self.split_link(r_part, 1);
+        self.root = self.merge_links(l_part, rr_part);
+        return self.storage.take(element.unwrap()).value;
+    }
+
+    /// moves the elements from index at to the end to new exray with the same functions, like Vec::split_off
+    /// it is O(log(N)) time, for ArenaExray the moved nodes are copied to new arena so it is O(M + log(N)) for M moved elements
+    pub fn split_off(&mut self, at: usize) -> Result<Self, ExrayError>
+    where
+        F: Clone,
+    {
+        if self.len() < at {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: at,
+                len: self.len(),
+            });
+        }
+
+        let root = self.root.take();
+        let (l_part, r_part) = self.split_link(root, at);
+        self.root = l_part;
+        let mut storage = S::default();
+        let root = storage.move_tree(r_part, &mut self.storage);
+        return Ok(Self {
+            root: root,
+            functions: self.functions.clone(),
+            ids: self.ids.clone(),
+            rev_functions: self.rev_functions.clone(),
+            storage: storage,
+            rng: RefCell::new(StdRng::seed_from_u64(self.rng.get_mut().gen::<u64>())),
+        });
+    }
+
+    /// moves the elements of other to the end of the exray, other should maintain the same functions and it stays empty with them
+    pub fn append(&mut self, other: &mut Self) -> Result<(), ExrayError> {
+        let len = self.len();
+        return self.insert_exray(other, len);
+    }
+
+    pub fn push_back(&mut self, value: T) {
+        let new_treap = self.make_node(value);
+        let root = self.root.take();
+        self.root = self.merge_links(root, new_treap);
+    }
+
+    pub fn push_front(&mut self, value: T) {
+        let new_treap = self.make_node(value);
+        let root = self.root.take();
+        self.root = self.merge_links(new_treap, root);
+    }
+
+    /// removes the last element and returns it, the pending actions are applied to it
+    pub fn pop_back(&mut self) -> Option<T> {
+        let len = self.len();
+        if len == 0 {
+            return None;
+        }
+        return Some(self.take_element(len - 1));
+    }
+
+    /// removes the first element and returns it, the pending actions are applied to it
+    pub fn pop_front(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        return Some(self.take_element(0));
+    }
+
+    /// keeps the first len elements and drops the others, if the exray is shorter nothing is changed
+    pub fn truncate(&mut self, len: usize) {
+        if self.len() <= len {
+            return;
+        }
+        let root = self.root.take();
+        let (l_part, mut r_part) = self.split_link(root, len);
+        self.root = l_part;
+        drop_treap(&mut r_part, &mut self.storage);
+    }
+
+    /// drops all elements, the functions are kept
+    pub fn clear(&mut self) {
+        drop_treap(&mut self.root, &mut self.storage);
+    }
+
+    /// swaps the elements with indices i and j by moving their nodes in O(log(N)) time
+    pub fn swap(&mut self, i: usize, j: usize) -> Result<(), ExrayError> {
+        if self.len() <= i || self.len() <= j {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: cmp::max(i, j),
+                len: self.len(),
+            });
+        }
+        if i == j {
+            return Ok(());
+        }
+        let (i, j) = (cmp::min(i, j), cmp::max(i, j));
+
+        // the exray is cut to [0; i), i, (i; j), j and (j; N), then they are merged with i and j swapped
+        let root = self.root.take();
+        let (before, rest) = self.split_link(root, i);
+        let (first, rest) = self.split_link(rest, 1);
+        let (between, rest) = self.split_link(rest, j - i - 1);
+        let (second, after) = self.split_link(rest, 1);
+
+        let mut curr = self.merge_links(before, second);
+        curr = self.merge_links(curr, between);
+        curr = self.merge_links(curr, first);
+        self.root = self.merge_links(curr, after);
+        return Ok(());
+    }
+
+    /// checks if other maintains the same functions, so its treap can be merged with the treap of the exray
+    fn check_compatible(&self, other: &Self) -> Result<(), ExrayError> {
+        let len = cmp::min(self.ids.len(), other.ids.len());
+        for i in 0..len {
+            if self.ids[i] != other.ids[i] {
+                return Err(ExrayError::FunctionMismatch { position: i });
+            }
+        }
+        // one of the exrays has more functions, the first extra function is the first difference
+        if self.ids.len() != other.ids.len() {
+            return Err(ExrayError::FunctionMismatch { position: len });
+        }
+        return Ok(());
+    }
+
+    /// replaces the elements in range with the treap new_part and returns the removed elements in new exray, with one pass of splits and merges
+    fn splice_link(&mut self, range: Range<usize>, new_part: Link<T, U, A, S>) -> Self
+    where
+        F: Clone,
+    {
+        let root = self.root.take();
+        let (l_part, r_part) = self.split_link(root, range.start);
+        let (removed, rr_part) = self.split_link(r_part, range.end - range.start);
+        let curr = self.merge_links(l_part, new_part);
+        self.root = self.merge_links(curr, rr_part);
+
+        let mut storage = S::default();
+        let root = storage.move_tree(removed, &mut self.storage);
+        return Self {
+            root: root,
+            functions: self.functions.clone(),
+            ids: self.ids.clone(),
+            rev_functions: self.rev_functions.clone(),
+            storage: storage,
+            rng: RefCell::new(StdRng::seed_from_u64(self.rng.get_mut().gen::<u64>())),
+        };
+    }
+
+    /// replaces the elements in range with the elements of replacement and returns the removed ones in new exray
+    /// the new elements are built into treap in O(M) time, so the whole splice is O(M + log(N)), for ArenaExray the removed nodes are also copied to new arena
+    pub fn splice<R: RangeBounds<usize>, I: IntoIterator<Item = T>>(
+        &mut self,
+        range: R,
+        replacement: I,
+    ) -> Result<Self, ExrayError>
+    where
+        F: Clone,
+    {
+        let range = resolve_range(range, self.len())?;
+        let new_part = build_treap(
+            replacement,
+            &self.functions,
+            &self.rev_functions,
+            self.rng.get_mut(),
+            &mut self.storage,
         );
-        let mut rl_part = None;
-        let mut rr_part = None;
-        split(
-            &mut r_part,
-            (end_ind as u64) - (beg_ind as u64) + 1,
-            &mut rl_part,
-            &mut rr_part,
+        return Ok(self.splice_link(range, new_part));
+    }
+
+    /// replaces the elements in range with the elements of replacement and returns the removed ones in new exray
+    /// replacement should maintain the same functions, its nodes are moved so after this it is empty like after insert_exray
+    /// it is O(log(N)) time, for ArenaExray the nodes of replacement and the removed nodes are copied between the arenas
+    pub fn splice_exray<R: RangeBounds<usize>>(
+        &mut self,
+        range: R,
+        replacement: &mut Self,
+    ) -> Result<Self, ExrayError>
+    where
+        F: Clone,
+    {
+        let range = resolve_range(range, self.len())?;
+        self.check_compatible(replacement)?;
+        let new_part = self
+            .storage
+            .move_tree(replacement.root.take(), &mut replacement.storage);
+        replacement.storage = S::default();
+        return Ok(self.splice_link(range, new_part));
+    }
+
+    /// moves the segment [beg_ind; end_ind] so its first element has index dest after the move, dest is counted as index in the exray without the segment
+    pub fn move_segment(
+        &mut self,
+        beg_ind: usize,
+        end_ind: usize,
+        dest: usize,
+    ) -> Result<(), ExrayError> {
+        if end_ind < beg_ind {
+            return Err(ExrayError::InvalidRange {
+                beg: beg_ind,
+                end: end_ind,
+            });
+        }
+        if self.len() <= end_ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: end_ind,
+                len: self.len(),
+            });
+        }
+        let segment_len = end_ind - beg_ind + 1;
+        if self.len() - segment_len < dest {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: dest,
+                len: self.len() - segment_len,
+            });
+        }
+
+        let root = self.root.take();
+        let (l_part, r_part) = self.split_link(root, beg_ind);
+        let (segment, rr_part) = self.split_link(r_part, segment_len);
+        let rest = self.merge_links(l_part, rr_part);
+        let (l_part, r_part) = self.split_link(rest, dest);
+        let curr = self.merge_links(l_part, segment);
+        self.root = self.merge_links(curr, r_part);
+        return Ok(());
+    }
+
+    /// rotates the elements in range, so the element with index range.start + k becomes first in the range, like slice::rotate_left
+    pub fn rotate_left<R: RangeBounds<usize>>(
+        &mut self,
+        range: R,
+        k: usize,
+    ) -> Result<(), ExrayError> {
+        let range = resolve_range(range, self.len())?;
+        if range.end - range.start < k {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: k,
+                len: range.end - range.start,
+            });
+        }
+
+        let root = self.root.take();
+        let (l_part, r_part) = self.split_link(root, range.start);
+        let (segment, rr_part) = self.split_link(r_part, range.end - range.start);
+        let (first, second) = self.split_link(segment, k);
+        let mut curr = self.merge_links(l_part, second);
+        curr = self.merge_links(curr, first);
+        self.root = self.merge_links(curr, rr_part);
+        return Ok(());
+    }
+
+    /// rotates the elements in range, so the last k elements of the range become first in it, like slice::rotate_right
+    pub fn rotate_right<R: RangeBounds<usize>>(
+        &mut self,
+        range: R,
+        k: usize,
+    ) -> Result<(), ExrayError> {
+        let range = resolve_range(range, self.len())?;
+        let len = range.end - range.start;
+        if len < k {
+            return Err(ExrayError::IndexOutOfBounds { index: k, len: len });
+        }
+        return self.rotate_left(range, len - k);
+    }
+
+    /// swaps the elements in the segments a and b, they can have different sizes but they should not overlap
+    /// empty segment is a position, so swapping it moves the other segment there, and it changes nothing when it is inside the other segment or at its ends
+    pub fn swap_segments<RA, RB>(&mut self, a: RA, b: RB) -> Result<(), ExrayError>
+    where
+        RA: RangeBounds<usize>,
+        RB: RangeBounds<usize>,
+    {
+        let a = resolve_range(a, self.len())?;
+        let b = resolve_range(b, self.len())?;
+        // the empty segment inside b or at its ends would be seen as overlap below, but b stays where it is
+        let inside = |x: &Range<usize>, y: &Range<usize>| {
+            x.start == x.end && y.start <= x.start && x.start <= y.end
+        };
+        if inside(&a, &b) || inside(&b, &a) {
+            return Ok(());
+        }
+        let (a, b) = if a.start <= b.start { (a, b) } else { (b, a) };
+        if b.start < a.end {
+            return Err(ExrayError::InvalidRange {
+                beg: b.start,
+                end: b.end,
+            });
+        }
+
+        // the exray is cut to the part before a, a, the part between them, b and the part after b
+        let root = self.root.take();
+        let (before, rest) = self.split_link(root, a.start);
+        let (first, rest) = self.split_link(rest, a.end - a.start);
+        let (between, rest) = self.split_link(rest, b.start - a.end);
+        let (second, after) = self.split_link(rest, b.end - b.start);
+
+        let mut curr = self.merge_links(before, second);
+        curr = self.merge_links(curr, between);
+        curr = self.merge_links(curr, first);
+        self.root = self.merge_links(curr, after);
+        return Ok(());
+    }
+
+    /// makes new exray from segment [beg_ind; end_ind] in log(N) time, for ArenaExray the extracted nodes are copied to new arena in O(M) time for M elements
+    pub fn extract_segment(&mut self, beg_ind: usize, end_ind: usize) -> Result<Self, ExrayError>
+    where
+        F: Clone,
+    {
+        if end_ind < beg_ind {
+            return Err(ExrayError::InvalidRange {
+                beg: beg_ind,
+                end: end_ind,
+            });
+        }
+        if self.len() <= end_ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: end_ind,
+                len: self.len(),
+            });
+        }
+        return self.extract_range(beg_ind..=end_ind);
+    }
+
+    /// makes new exray from the elements in range in log(N) time, for empty range the new exray is empty
+    /// for ArenaExray the extracted nodes are copied to new arena in O(M) time for M elements
+    pub fn extract_range<R: RangeBounds<usize>>(&mut self, range: R) -> Result<Self, ExrayError>
+    where
+        F: Clone,
+    {
+        let range = resolve_range(range, self.len())?;
+        return Ok(self.splice_link(range, None));
+    }
+
+    /// clones segment [beg_ind; end_ind] into new exray
+    pub fn clone_segment(&self, beg_ind: usize, end_ind: usize) -> Result<Self, ExrayError>
+    where
+        T: Clone,
+        U: Clone,
+        F: Clone,
+    {
+        if end_ind < beg_ind {
+            return Err(ExrayError::InvalidRange {
+                beg: beg_ind,
+                end: end_ind,
+            });
+        }
+        if self.len() <= end_ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: end_ind,
+                len: self.len(),
+            });
+        }
+        return self.clone_range(beg_ind..=end_ind);
+    }
+
+    /// clones the elements in range into new exray, the treap is not changed - the O(log(N)) subtrees covering the range are copied like in range_functions_values
+    pub fn clone_range<R: RangeBounds<usize>>(&self, range: R) -> Result<Self, ExrayError>
+    where
+        T: Clone,
+        U: Clone,
+        F: Clone,
+    {
+        let range = resolve_range(range, self.len())?;
+        let seed = self.rng.borrow_mut().gen::<u64>();
+        let mut rng = StdRng::seed_from_u64(seed);
+        let (new_storage, new_root) = clone_segment(
+            &self.root,
+            range.start as u64,
+            range.end as u64,
             &self.functions,
+            &self.rev_functions,
+            &mut rng,
+            &self.storage,
         );
 
-        let mut values: Vec<U>;
-        values = vec![];
-        values.extend_from_slice(get_values(&rl_part));
+        return Ok(Self {
+            root: new_root,
+            functions: self.functions.clone(),
+            ids: self.ids.clone(),
+            rev_functions: self.rev_functions.clone(),
+            storage: new_storage,
+            rng: RefCell::new(rng),
+        });
+    }
 
-        merge(&mut r_part, &mut rl_part, &mut rr_part, &self.functions);
-        merge(&mut self.root, &mut l_part, &mut r_part, &self.functions);
+    /// returns functions values for the segment [beg_ind; end_ind] in Vector, needs the return type of functions to be cloneable
+    pub fn segment_functions_values(
+        &self,
+        beg_ind: usize,
+        end_ind: usize,
+    ) -> Result<Vec<U>, ExrayError>
+    where
+        U: Clone,
+    {
+        if end_ind < beg_ind {
+            return Err(ExrayError::InvalidRange {
+                beg: beg_ind,
+                end: end_ind,
+            });
+        }
+        if self.len() <= end_ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: end_ind,
+                len: self.len(),
+            });
+        }
+        return self.range_functions_values(beg_ind..=end_ind);
+    }
 
-        return Ok(values);
+    /// returns functions values for the elements in range, for empty range they are the identities of the functions
+    /// the treap is not changed - the values of the O(log(N)) subtrees covering the range are combined while descending from the root
+    pub fn range_functions_values<R: RangeBounds<usize>>(
+        &self,
+        range: R,
+    ) -> Result<Vec<U>, ExrayError>
+    where
+        U: Clone,
+    {
+        let range = resolve_range(range, self.len())?;
+        return Ok(query_segment(
+            &self.root,
+            range.start as u64,
+            range.end as u64,
+            &self.functions,
+            &self.rev_functions,
+            &self.storage,
+        ));
     }
 
     /// returns slice to functions values for all elements in the exray
+    /// the exrays made with with_aggregate have one typed function, so their value is read with aggregate_value instead of taking it out of the slice
     pub fn functions_values(&self) -> &[U] {
         if self.len() == 0 {
             return &[];
         }
-        return get_values(&self.root);
+        return get_values(&self.root, &self.storage);
+    }
+
+    /// returns the value of the first maintained function for all elements, for exrays made with with_aggregate this is the typed value of the aggregate
+    /// for empty exray the identity of the function is returned, NoFunctions is returned if the exray doesn't maintain any functions
+    pub fn aggregate_value(&self) -> Result<U, ExrayError>
+    where
+        U: Clone,
+    {
+        if self.functions.is_empty() {
+            return Err(ExrayError::NoFunctions);
+        }
+        if self.is_empty() {
+            return Ok(self.functions[0].identity());
+        }
+        return Ok(get_values(&self.root, &self.storage)[0].clone());
+    }
+
+    /// returns the value of the first maintained function for the segment [beg_ind; end_ind], like aggregate_value
+    pub fn segment_aggregate_value(&self, beg_ind: usize, end_ind: usize) -> Result<U, ExrayError>
+    where
+        U: Clone,
+    {
+        if self.functions.is_empty() {
+            return Err(ExrayError::NoFunctions);
+        }
+        let mut values = self.segment_functions_values(beg_ind, end_ind)?;
+        return Ok(values.swap_remove(0));
+    }
+
+    /// returns the value of the first maintained function for the elements in range, like aggregate_value
+    pub fn range_aggregate_value<R: RangeBounds<usize>>(&self, range: R) -> Result<U, ExrayError>
+    where
+        U: Clone,
+    {
+        if self.functions.is_empty() {
+            return Err(ExrayError::NoFunctions);
+        }
+        let mut values = self.range_functions_values(range)?;
+        return Ok(values.swap_remove(0));
+    }
+
+    /// returns the biggest end such that pred is true for the functions values of the segment [start; end), the segment is empty for end equal to start
+    /// pred should be monotone - if it is true for some segment it should be true for the shorter segments from start, then the boundary is found in O(log(N)) time
+    /// for example with sum and non-negative elements max_right(0, |v| v[0] <= x) is the length of the longest prefix with sum at most x
+    pub fn max_right<P>(&self, start: usize, pred: P) -> Result<usize, ExrayError>
+    where
+        U: Clone,
+        P: FnMut(&[U]) -> bool,
+    {
+        if self.len() < start {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: start,
+                len: self.len(),
+            });
+        }
+        return Ok(max_right(
+            &self.root,
+            start as u64,
+            pred,
+            &self.functions,
+            &self.rev_functions,
+            &self.storage,
+        ) as usize);
+    }
+
+    /// returns the smallest start such that pred is true for the functions values of the segment [start; end), it is like max_right but the segment grows to the left
+    pub fn min_left<P>(&self, end: usize, pred: P) -> Result<usize, ExrayError>
+    where
+        U: Clone,
+        P: FnMut(&[U]) -> bool,
+    {
+        if self.len() < end {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: end,
+                len: self.len(),
+            });
+        }
+        return Ok(min_left(
+            &self.root,
+            end as u64,
+            pred,
+            &self.functions,
+            &self.rev_functions,
+            &self.storage,
+        ) as usize);
     }
 
     /// this functions should be used after changing element with index to recover function values in the treap
     pub fn recover_fvalues(&mut self, ind: usize) -> Result<(), ExrayError> {
         if self.len() <= ind {
-            return Err(ExrayError::IndexError(String::from(
-                "Index greater than last index!",
-            )));
+            return Err(ExrayError::IndexOutOfBounds {
+                index: ind,
+                len: self.len(),
+            });
         }
 
-        let mut l_part = None;
-        let mut r_part = None;
-        split(
+        // only the nodes on the path to the element are recovered, the treap is not split
+        let mut path = Vec::<bool>::new();
+        push_path(
             &mut self.root,
             ind as u64,
-            &mut l_part,
-            &mut r_part,
+            &self.rev_functions,
+            &mut self.storage,
+            &mut path,
+        );
+        recover_path(
+            &mut self.root,
+            &path,
             &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
         );
-        let mut rl_part = None;
-        let mut rr_part = None;
-        split(&mut r_part, 1, &mut rl_part, &mut rr_part, &self.functions);
+        return Ok(());
+    }
+
+    /// applies action to all elements in the segment [beg_ind; end_ind] in log(N) time, the action is pushed lazily to the nodes
+    pub fn update_segment(
+        &mut self,
+        beg_ind: usize,
+        end_ind: usize,
+        action: A,
+    ) -> Result<(), ExrayError> {
+        if end_ind < beg_ind {
+            return Err(ExrayError::InvalidRange {
+                beg: beg_ind,
+                end: end_ind,
+            });
+        }
+        if self.len() <= end_ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: end_ind,
+                len: self.len(),
+            });
+        }
+        return self.update_range(beg_ind..=end_ind, action);
+    }
+
+    /// applies action to all elements in range in log(N) time, for empty range nothing is changed
+    pub fn update_range<R: RangeBounds<usize>>(
+        &mut self,
+        range: R,
+        action: A,
+    ) -> Result<(), ExrayError> {
+        let range = resolve_range(range, self.len())?;
+        let root = self.root.take();
+        let (l_part, r_part) = self.split_link(root, range.start);
+        let (mut segment, rr_part) = self.split_link(r_part, range.end - range.start);
+        apply_action(
+            &mut segment,
+            &action,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+        let curr = self.merge_links(l_part, segment);
+        self.root = self.merge_links(curr, rr_part);
+        return Ok(());
+    }
+
+    /// reverses the segment [beg_ind; end_ind] in log(N) time, the reversal is pushed lazily to the nodes
+    /// the values of the non-commutative functions stay correct only if their is_commutative returns false
+    pub fn reverse_segment(&mut self, beg_ind: usize, end_ind: usize) -> Result<(), ExrayError> {
+        if end_ind < beg_ind {
+            return Err(ExrayError::InvalidRange {
+                beg: beg_ind,
+                end: end_ind,
+            });
+        }
+        if self.len() <= end_ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: end_ind,
+                len: self.len(),
+            });
+        }
+        return self.reverse_range(beg_ind..=end_ind);
+    }
+
+    /// reverses the elements in range in log(N) time, for empty range nothing is changed
+    pub fn reverse_range<R: RangeBounds<usize>>(&mut self, range: R) -> Result<(), ExrayError> {
+        let range = resolve_range(range, self.len())?;
+        let root = self.root.take();
+        let (l_part, r_part) = self.split_link(root, range.start);
+        let (mut segment, rr_part) = self.split_link(r_part, range.end - range.start);
+        apply_rev(&mut segment, &self.rev_functions, &mut self.storage);
+        let curr = self.merge_links(l_part, segment);
+        self.root = self.merge_links(curr, rr_part);
+        return Ok(());
+    }
+
+    /// returns the indices of the non-commutative functions
+    pub fn non_commutative(&self) -> &[usize] {
+        &self.rev_functions
+    }
+
+    /// checks the invariants of the treap - the y_keys are in heap order, every cnt is the size of its subtree and every value_all is recomputed from the children
+    /// it visits all nodes so it is O(N) and it is meant for tests and debugging
+    pub fn validate(&self) -> Result<(), InvariantError>
+    where
+        U: Clone + PartialEq,
+    {
+        return validate_treap(
+            &self.root,
+            &self.functions,
+            &self.rev_functions,
+            &self.storage,
+        );
+    }
+
+    /// returns the number of nodes, the depth of the treap and the bytes used by the nodes and their values
+    pub fn stats(&self) -> ExrayStats {
+        return treap_stats(&self.root, &self.storage);
+    }
+
+    /// returns the treap in the dot language of Graphviz with the sizes and the function values of the nodes
+    pub fn to_dot(&self) -> String
+    where
+        T: Debug,
+        U: Debug,
+    {
+        return treap_dot(&self.root, &self.storage);
+    }
 
-        merge(&mut r_part, &mut rl_part, &mut rr_part, &self.functions);
-        merge(&mut self.root, &mut l_part, &mut r_part, &self.functions);
+    /// returns the element at ind, the pending actions on the path to it are applied first so unlike Index this works for exrays with any action
+    pub fn get_element(&mut self, ind: usize) -> Result<&T, ExrayError> {
+        if self.len() <= ind {
+            return Err(ExrayError::IndexOutOfBounds {
+                index: ind,
+                len: self.len(),
+            });
+        }
+        return Ok(find_mut_index(
+            &mut self.root,
+            ind as u64,
+            &self.rev_functions,
+            &mut self.storage,
+        ));
+    }
+
+    /// returns the first element or None for empty exray, like get_element the pending actions on the path to it are applied first
+    pub fn first(&mut self) -> Option<&T> {
+        return self.get_element(0).ok();
+    }
+
+    /// returns the last element or None for empty exray, like get_element the pending actions on the path to it are applied first
+    pub fn last(&mut self) -> Option<&T> {
+        let len = self.len();
+        if len == 0 {
+            return None;
+        }
+        return self.get_element(len - 1).ok();
+    }
+
+    /// returns guard for changing the element with index ind, the function values are recovered when the guard is dropped
+    /// None is returned if the index is out of bounds
+    pub fn get_mut(&mut self, ind: usize) -> Option<ElementMut<'_, T, U, A, F, S>> {
+        if self.len() <= ind {
+            return None;
+        }
+        // the tags on the path are pushed once here, the guard reuses the path for reading, changing and recovering the element
+        let path = self.push_element_path(ind);
+        return Some(ElementMut::new(self, path));
+    }
 
+    /// changes the element with index ind with the function change and recovers the function values
+    pub fn update<G: FnOnce(&mut T)>(&mut self, ind: usize, change: G) -> Result<(), ExrayError> {
+        let len = self.len();
+        let mut element = match self.get_mut(ind) {
+            None => {
+                return Err(ExrayError::IndexOutOfBounds {
+                    index: ind,
+                    len: len,
+                })
+            }
+            Some(element) => element,
+        };
+        change(&mut element);
         return Ok(());
     }
 
+    /// pushes the tags on the path to the element with index ind and returns the path from the root, false is for the left child and true for the right child
+    pub(super) fn push_element_path(&mut self, ind: usize) -> Vec<bool> {
+        let mut path = Vec::<bool>::new();
+        push_path(
+            &mut self.root,
+            ind as u64,
+            &self.rev_functions,
+            &mut self.storage,
+            &mut path,
+        );
+        return path;
+    }
+
+    /// returns the element at the end of path, the tags on the path should be pushed with push_element_path
+    pub(super) fn path_element(&self, path: &[bool]) -> &T {
+        find_path(&self.root, path, &self.storage)
+    }
+
+    /// returns the element at the end of path for changing it, the function values are not recovered
+    pub(super) fn path_element_mut(&mut self, path: &[bool]) -> &mut T {
+        find_mut_path(&mut self.root, path, &mut self.storage)
+    }
+
+    /// recovers the function values on path after the element at its end is changed
+    pub(super) fn recover_path(&mut self, path: &[bool]) {
+        recover_path(
+            &mut self.root,
+            path,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+    }
+
+    /// recovers the function values after the elements in the segment [beg; end) are changed
+    pub(super) fn recover_segment(&mut self, beg: usize, end: usize) {
+        recover_segment(
+            &mut self.root,
+            beg as u64,
+            end as u64,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+    }
+}
+
+impl<T, U, A: Action<T, U>, F: Aggregate<T, Value = U>, S: PointerStorage<T, U, A>>
+    Exray<T, U, A, F, S>
+{
+    /// calls change with IterMut over the elements in order and returns its result, the function values are recovered after it, also if it panics
+    /// the references to the elements cannot leave change, so they cannot be changed after the recovery
+    pub fn iter_mut<R, G: FnOnce(IterMut<'_, T, U, A, S>) -> R>(&mut self, change: G) -> R {
+        let len = self.len();
+        let mut guard = RecoverGuard::new(self, 0, len);
+        return change(guard.values());
+    }
+
+    /// iterator over the elements in the segment [beg; end), the function values are not recovered by it
+    pub(super) fn values_mut(&mut self, beg: usize, end: usize) -> IterMut<'_, T, U, A, S> {
+        return IterMut::new(
+            &mut self.root,
+            &mut self.storage,
+            &self.rev_functions,
+            beg,
+            end,
+        );
+    }
+}
+
+/// the methods reading elements through shared reference are only for exrays without actions, because the pending actions cannot be pushed without changing the treap
+impl<T, U, F, S: Storage<T, U, NoAction>> Exray<T, U, NoAction, F, S> {
     pub fn to_vec(&self) -> Vec<&T> {
         let mut result = Vec::<&T>::new();
-        collect_elements(&self.root, &mut result);
+        collect_elements(&self.root, &mut result, false, &self.storage);
         return result;
     }
+
+    /// returns the number of elements for which pred is true in O(log(N)) time, pred should be true for some prefix of the exray and false for the rest of it
+    pub fn partition_point<P: FnMut(&T) -> bool>(&self, pred: P) -> usize {
+        return partition_point(&self.root, pred, &self.storage) as usize;
+    }
+
+    /// returns the element with index ind or None if the index is out of bounds
+    pub fn get(&self, ind: usize) -> Option<&T> {
+        if (get_cnt(&self.root, &self.storage) as usize) <= ind {
+            return None;
+        }
+        return Some(find_index(&self.root, ind as u64, false, &self.storage));
+    }
+
+    /// returns view of the elements in range or None if the range is not inside the exray
+    pub fn get_segment_view<R: RangeBounds<usize>>(
+        &self,
+        range: R,
+    ) -> Option<SegmentView<'_, T, U, F, S>> {
+        let len = get_cnt(&self.root, &self.storage) as usize;
+        return match resolve_range(range, len) {
+            Err(_) => None,
+            Ok(range) => Some(SegmentView::new(self, range.start, range.end)),
+        };
+    }
+
+    /// returns iterator over the elements in order, every step is O(1) amortized
+    /// the pending tags cannot be pushed through &self, so the elements of exrays with actions are iterated with iter_mut or into_iter
+    pub fn iter(&self) -> Iter<'_, T, U, S> {
+        let len = get_cnt(&self.root, &self.storage) as usize;
+        return Iter::new(&self.root, &self.storage, 0, len);
+    }
+
+    /// returns iterator over the elements with indices in range, the beginning of the range is found in O(log(N)) time
+    pub fn iter_range<R: RangeBounds<usize>>(
+        &self,
+        range: R,
+    ) -> Result<Iter<'_, T, U, S>, ExrayError> {
+        let len = get_cnt(&self.root, &self.storage) as usize;
+        let range = resolve_range(range, len)?;
+        return Ok(Iter::new(&self.root, &self.storage, range.start, range.end));
+    }
 }
 
-use std::ops::Index;
-impl<T, U> Index<usize> for Exray<T, U> {
-    type Output = T;
+/// the elements are built into treap in O(N) time and merged at the end of the exray
+impl<T, U, A: Action<T, U>, F: Aggregate<T, Value = U>, S: Storage<T, U, A>> Extend<T>
+    for Exray<T, U, A, F, S>
+{
+    fn extend<I: IntoIterator<Item = T>>(&mut self, elements: I) {
+        let mut new_part = build_treap(
+            elements,
+            &self.functions,
+            &self.rev_functions,
+            self.rng.get_mut(),
+            &mut self.storage,
+        );
+        let mut temp = None;
+        merge(
+            &mut temp,
+            &mut self.root,
+            &mut new_part,
+            &self.functions,
+            &self.rev_functions,
+            &mut self.storage,
+        );
+        self.root = temp;
+    }
+}
 
-    fn index(&self, ind: usize) -> &Self::Output {
-        find_index(&self.root, ind as u64)
+/// the collected exray doesn't maintain any functions
+impl<T, U, A: Action<T, U>, F: Aggregate<T, Value = U>, S: Storage<T, U, A>> FromIterator<T>
+    for Exray<T, U, A, F, S>
+{
+    fn from_iter<I: IntoIterator<Item = T>>(elements: I) -> Self {
+        let mut exray = Self::new(vec![], vec![]);
+        exray.extend(elements);
+        return exray;
+    }
+}
+
+impl<'a, T, U, F, S: Storage<T, U, NoAction>> IntoIterator for &'a Exray<T, U, NoAction, F, S> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, U, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
     }
 }
-use std::ops::IndexMut;
-impl<T, U> IndexMut<usize> for Exray<T, U> {
-    // after changing value, recover_fvalues at ind should be called!
-    fn index_mut(&mut self, ind: usize) -> &mut Self::Output {
-        find_mut_index(&mut self.root, ind as u64)
+
+/// the exray is consumed and its elements are taken out in order, the pending actions are applied to them
+impl<T, U, A: Action<T, U>, F, S: Storage<T, U, A>> IntoIterator for Exray<T, U, A, F, S> {
+    type Item = T;
+    type IntoIter = IntoIter<T, U, A, S>;
+
+    fn into_iter(mut self) -> Self::IntoIter {
+        let root = self.root.take();
+        let storage = mem::take(&mut self.storage);
+        let rev_functions = mem::take(&mut self.rev_functions);
+        IntoIter::new(root, storage, rev_functions)
     }
 }
 
-impl<T, U> Drop for Exray<T, U> {
+use std::ops::Index;
+impl<T, U, F, S: Storage<T, U, NoAction>> Index<usize> for Exray<T, U, NoAction, F, S> {
+    type Output = T;
+
+    fn index(&self, ind: usize) -> &Self::Output {
+        match self.get(ind) {
+            None => panic!(
+                "index out of bounds: len is {} but the index is {}",
+                get_cnt(&self.root, &self.storage),
+                ind
+            ),
+            Some(element) => element,
+        }
+    }
+}
+impl<T, U, A, F, S: Storage<T, U, A>> Drop for Exray<T, U, A, F, S> {
     fn drop(&mut self) {
-        drop_treap(&mut self.root);
+        drop_treap(&mut self.root, &mut self.storage);
     }
 }
 
-impl<T: Clone, U: Clone> Clone for Exray<T, U> {
+/// the exray is copied by its storage, for BoxStorage all nodes are copied and for RcStorage only the root is shared
+/// like the exrays made by split_off and extract_segment the clone gets rng seeded from the rng of the exray, so their y_keys are different but still determined by the seed of with_rng
+/// the seed is drawn from the rng of the exray, so it moves on and the next clones get other seeds
+impl<T: Clone, U: Clone, A: Action<T, U>, F: Clone, S: Storage<T, U, A>> Clone
+    for Exray<T, U, A, F, S>
+{
     fn clone(&self) -> Self {
-        Exray::<T, U> {
-            root: clone_treap(&self.root),
+        let seed = self.rng.borrow_mut().gen::<u64>();
+        let (storage, root) = self.storage.clone_tree(&self.root);
+        Exray::<T, U, A, F, S> {
+            root: root,
             functions: self.functions.clone(),
+            ids: self.ids.clone(),
+            rev_functions: self.rev_functions.clone(),
+            storage: storage,
+            rng: RefCell::new(StdRng::seed_from_u64(seed)),
         }
     }
 }
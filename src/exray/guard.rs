@@ -0,0 +1,69 @@
+/// Here is the guard for changing a single element of the exray, the function values are recovered when it is dropped
+use std::ops::{Deref, DerefMut};
+
+use crate::exray::aggregate::Aggregate;
+use crate::exray::exray::Exray;
+use crate::exray::treap::*;
+
+/// ElementMut borrows the exray for changing one element, it is used like &mut T:
+/// *exray.get_mut(ind).unwrap() = value;
+/// the tags on the path to the element are pushed once when the guard is made and the path is kept, so derefs only follow it and the drop recovers the function values on it
+pub struct ElementMut<'a, T, U, A, F, S>
+where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, A>,
+{
+    exray: &'a mut Exray<T, U, A, F, S>,
+    path: Vec<bool>,
+}
+
+impl<'a, T, U, A, F, S> ElementMut<'a, T, U, A, F, S>
+where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, A>,
+{
+    /// path is the path from the root to the element, the tags on it should be pushed
+    pub(super) fn new(exray: &'a mut Exray<T, U, A, F, S>, path: Vec<bool>) -> Self {
+        ElementMut {
+            exray: exray,
+            path: path,
+        }
+    }
+}
+
+impl<'a, T, U, A, F, S> Deref for ElementMut<'a, T, U, A, F, S>
+where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, A>,
+{
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        self.exray.path_element(&self.path)
+    }
+}
+
+impl<'a, T, U, A, F, S> DerefMut for ElementMut<'a, T, U, A, F, S>
+where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, A>,
+{
+    fn deref_mut(&mut self) -> &mut T {
+        self.exray.path_element_mut(&self.path)
+    }
+}
+
+impl<'a, T, U, A, F, S> Drop for ElementMut<'a, T, U, A, F, S>
+where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, A>,
+{
+    fn drop(&mut self) {
+        self.exray.recover_path(&self.path);
+    }
+}
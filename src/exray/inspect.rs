@@ -0,0 +1,281 @@
+/// Here are the functions for inspecting the treap of the exray - checking its invariants, measuring it and drawing it for Graphviz
+/// they only read the nodes, so the pending tags and reversals are taken into account instead of being pushed
+use std::cmp;
+use std::error::Error;
+use std::fmt;
+use std::fmt::Debug;
+use std::fmt::Write;
+use std::mem;
+
+use crate::exray::aggregate::Aggregate;
+use crate::exray::treap::*;
+
+/// the broken invariants of the treap, index is the index in the exray of the element in the node where the check failed
+#[derive(Debug, PartialEq)]
+pub enum InvariantError {
+    /// the y_key of the node is bigger than the y_key of its parent
+    HeapOrder { index: usize },
+    /// the cnt of the node is not the size of its subtree
+    Count {
+        index: usize,
+        cnt: u64,
+        expected: u64,
+    },
+    /// the node doesn't have one value for every function or one reversed value for every non-commutative function
+    ValueCount { index: usize },
+    /// value_all of the node for the function with index function is not the value computed from its children
+    Value { index: usize, function: usize },
+    /// value_all_rev of the node for the function with index function is not the value computed from its children
+    ReversedValue { index: usize, function: usize },
+}
+
+impl fmt::Display for InvariantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvariantError::HeapOrder { index } => {
+                write!(
+                    f,
+                    "the node of element {} has bigger y_key than its parent",
+                    index
+                )
+            }
+            InvariantError::Count {
+                index,
+                cnt,
+                expected,
+            } => write!(
+                f,
+                "the node of element {} has cnt {} but its subtree has {} elements",
+                index, cnt, expected
+            ),
+            InvariantError::ValueCount { index } => write!(
+                f,
+                "the node of element {} has wrong number of function values",
+                index
+            ),
+            InvariantError::Value { index, function } => write!(
+                f,
+                "the node of element {} has wrong value of function {}",
+                index, function
+            ),
+            InvariantError::ReversedValue { index, function } => write!(
+                f,
+                "the node of element {} has wrong reversed value of function {}",
+                index, function
+            ),
+        }
+    }
+}
+
+impl Error for InvariantError {}
+
+/// the sizes of the treap, depth is the number of nodes on the longest path from the root and bytes are the memory of the nodes and their values
+#[derive(Debug, Clone, PartialEq)]
+pub struct ExrayStats {
+    pub nodes: usize,
+    pub depth: usize,
+    pub bytes: usize,
+}
+
+/// the node of the treap with its index in the exray
+type IndexedNode<'a, T, U, A, S> = (&'a ImplicitTreap<T, U, A, S>, usize);
+
+/// returns the nodes of the treap in pre-order with their indices in the exray
+/// flip shows that the subtree should be reversed because of the pending reversals above it, like in find_index
+fn collect_nodes<'a, T, U, A, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    storage: &'a S,
+) -> Vec<IndexedNode<'a, T, U, A, S>> {
+    let mut nodes = Vec::<IndexedNode<'a, T, U, A, S>>::new();
+    let mut stack = vec![(curr, 0, false)];
+    while let Some((sub, offset, flip)) = stack.pop() {
+        let ptr = match sub {
+            None => continue,
+            Some(ptr) => ptr,
+        };
+        let node = storage.get(ptr);
+        let (l, r) = if flip {
+            (&node.r, &node.l)
+        } else {
+            (&node.l, &node.r)
+        };
+        let curr_ind = offset + get_cnt(l, storage);
+        nodes.push((node, curr_ind as usize));
+        stack.push((r, curr_ind + 1, flip ^ node.rev));
+        stack.push((l, offset, flip ^ node.rev));
+    }
+    return nodes;
+}
+
+/// returns the values of the child of node as they are seen from the node - with its pending reversal and tag applied
+/// the first Vector is for value_all and the second is for value_all_rev
+fn child_values<T, U: Clone, A: Action<T, U>, S: Storage<T, U, A>>(
+    node: &ImplicitTreap<T, U, A, S>,
+    child: &Link<T, U, A, S>,
+    rev_functions: &[usize],
+    storage: &S,
+) -> Option<(Vec<U>, Vec<U>, u64)> {
+    let child = match child {
+        None => return None,
+        Some(ptr) => storage.get(ptr),
+    };
+    let mut values: Vec<U> = child.value_all.to_vec();
+    let mut values_rev: Vec<U> = child.value_all_rev.to_vec();
+    if node.rev {
+        for (k, &i) in rev_functions.iter().enumerate() {
+            mem::swap(&mut values[i], &mut values_rev[k]);
+        }
+    }
+    if let Some(tag) = &node.tag {
+        for (i, value) in values.iter_mut().enumerate() {
+            tag.apply_fvalue(i, value, child.cnt);
+        }
+        for (k, value) in values_rev.iter_mut().enumerate() {
+            tag.apply_fvalue(rev_functions[k], value, child.cnt);
+        }
+    }
+    return Some((values, values_rev, child.cnt));
+}
+
+/// checks the invariants of the treap - the heap order of the y_keys, the sizes of the subtrees and the values of the functions
+/// the structure is checked for all nodes first, so the values of the children can be read when the values are recomputed
+pub fn validate_treap<T, U, A, F, S>(
+    curr: &Link<T, U, A, S>,
+    functions: &[F],
+    rev_functions: &[usize],
+    storage: &S,
+) -> Result<(), InvariantError>
+where
+    U: Clone + PartialEq,
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, A>,
+{
+    let nodes = collect_nodes(curr, storage);
+    for &(node, index) in nodes.iter() {
+        for ptr in [&node.l, &node.r].iter().copied().flatten() {
+            if storage.get(ptr).y_key > node.y_key {
+                return Err(InvariantError::HeapOrder { index: index });
+            }
+        }
+        let expected = get_cnt(&node.l, storage) + get_cnt(&node.r, storage) + 1;
+        if node.cnt != expected {
+            return Err(InvariantError::Count {
+                index: index,
+                cnt: node.cnt,
+                expected: expected,
+            });
+        }
+        if node.value_all.len() != functions.len()
+            || node.value_all_rev.len() != rev_functions.len()
+        {
+            return Err(InvariantError::ValueCount { index: index });
+        }
+    }
+
+    for &(node, index) in nodes.iter() {
+        let l = child_values(node, &node.l, rev_functions, storage);
+        let r = child_values(node, &node.r, rev_functions, storage);
+        for (i, function) in functions.iter().enumerate() {
+            let l_data = l.as_ref().map(|(values, _, cnt)| (&values[i], *cnt));
+            let r_data = r.as_ref().map(|(values, _, cnt)| (&values[i], *cnt));
+            if function.compute(l_data, &node.value, r_data) != node.value_all[i] {
+                return Err(InvariantError::Value {
+                    index: index,
+                    function: i,
+                });
+            }
+        }
+        // like in recover the reversed right part is on the left and the reversed left part is on the right
+        for (k, &i) in rev_functions.iter().enumerate() {
+            let l_data = r.as_ref().map(|(_, values, cnt)| (&values[k], *cnt));
+            let r_data = l.as_ref().map(|(_, values, cnt)| (&values[k], *cnt));
+            if functions[i].compute(l_data, &node.value, r_data) != node.value_all_rev[k] {
+                return Err(InvariantError::ReversedValue {
+                    index: index,
+                    function: i,
+                });
+            }
+        }
+    }
+    return Ok(());
+}
+
+/// returns the number of nodes, the depth and the memory of the treap
+/// the bytes are the sizes of the nodes and the memory that their values have outside of the nodes, the shared nodes of persistent exrays are counted in every exray
+pub fn treap_stats<T, U, A, S: Storage<T, U, A>>(
+    curr: &Link<T, U, A, S>,
+    storage: &S,
+) -> ExrayStats {
+    let mut stats = ExrayStats {
+        nodes: 0,
+        depth: 0,
+        bytes: 0,
+    };
+    let mut stack = vec![(curr, 1)];
+    while let Some((sub, depth)) = stack.pop() {
+        let node = match sub {
+            None => continue,
+            Some(ptr) => storage.get(ptr),
+        };
+        stats.nodes += 1;
+        stats.depth = cmp::max(stats.depth, depth);
+        stats.bytes += mem::size_of::<ImplicitTreap<T, U, A, S>>()
+            + node.value_all.heap_bytes()
+            + node.value_all_rev.heap_bytes();
+        stack.push((&node.r, depth + 1));
+        stack.push((&node.l, depth + 1));
+    }
+    return stats;
+}
+
+/// escapes the text for a quoted string in the dot language, the line breaks are kept as \n
+fn escape_dot(text: &str) -> String {
+    return text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n");
+}
+
+/// returns the treap in the dot language of Graphviz, every node shows its element, cnt, value_all and value_all_rev if there are non-commutative functions
+/// the edges are the links as they are stored, so the children of the nodes marked with rev are still not reversed
+pub fn treap_dot<T: Debug, U: Debug, A, S: Storage<T, U, A>>(
+    curr: &Link<T, U, A, S>,
+    storage: &S,
+) -> String {
+    let mut dot = String::from("digraph exray {\n    node [shape=box];\n");
+    // the nodes are named by their order in the traversal, the parent of the root is None
+    let mut names = 0;
+    let mut stack = vec![(curr, None, "")];
+    while let Some((sub, parent, side)) = stack.pop() {
+        let node = match sub {
+            None => continue,
+            Some(ptr) => storage.get(ptr),
+        };
+        let name = names;
+        names += 1;
+        let mut label = format!(
+            "{:?}\ncnt: {}\nvalues: {:?}",
+            node.value, node.cnt, &*node.value_all
+        );
+        // value_all_rev is empty when all functions are commutative
+        if !node.value_all_rev.is_empty() {
+            write!(label, "\nreversed values: {:?}", &*node.value_all_rev).unwrap();
+        }
+        if node.rev {
+            label.push_str("\nrev");
+        }
+        if node.tag.is_some() {
+            label.push_str("\ntag");
+        }
+        // writing to String can't fail
+        writeln!(dot, "    n{} [label=\"{}\"];", name, escape_dot(&label)).unwrap();
+        if let Some(parent) = parent {
+            writeln!(dot, "    n{} -> n{} [label=\"{}\"];", parent, name, side).unwrap();
+        }
+        stack.push((&node.r, Some(name), "r"));
+        stack.push((&node.l, Some(name), "l"));
+    }
+    dot.push_str("}\n");
+    return dot;
+}
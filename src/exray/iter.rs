@@ -0,0 +1,471 @@
+/// Here are the iterators over the exray, instead of recursion they keep explicit deque with the parts of the treap that are not visited yet
+use std::collections::VecDeque;
+
+use crate::exray::aggregate::Aggregate;
+use crate::exray::exray::Exray;
+use crate::exray::treap::*;
+
+/// the parts of the treap that are not visited yet - single elements and whole subtrees
+/// the iterators take parts from the front and the back of the deque and expand the subtrees until there is a single element at the end they need
+enum Pending<V, L> {
+    Value(V),
+    Tree(L),
+}
+
+/// the left child, the element and the right child of the expanded node
+type Expanded<L, V> = (L, V, L);
+
+/// the subtree of Iter together with its flip
+type FlipTree<'a, T, U, S> = (&'a Link<T, U, NoAction, S>, bool);
+
+/// the parts of IterMut, they are mutable references into the treap
+type PendingMut<'a, T, U, A, S> = Pending<&'a mut T, &'a mut Link<T, U, A, S>>;
+
+/// iterator over references to the elements, like Index it is only for exrays without actions
+/// the stored elements of exray with actions can miss the pending tags of their ancestors and Iter cannot push them through &self, so such exrays are iterated with iter_mut or into_iter
+/// for the subtrees the iterator keeps also flip which shows if the subtree should be reversed because of the pending reversals above it
+pub struct Iter<'a, T, U, S: Storage<T, U, NoAction>> {
+    pending: VecDeque<Pending<&'a T, FlipTree<'a, T, U, S>>>,
+    storage: &'a S,
+    /// number of elements that are not visited yet
+    len: usize,
+}
+
+impl<'a, T, U: 'a, S: Storage<T, U, NoAction>> Iter<'a, T, U, S> {
+    /// makes iterator over the elements with indices in [beg; end) of the treap root
+    pub(super) fn new(
+        root: &'a Link<T, U, NoAction, S>,
+        storage: &'a S,
+        beg: usize,
+        end: usize,
+    ) -> Self {
+        let mut iter = Iter {
+            pending: VecDeque::new(),
+            storage: storage,
+            len: get_cnt(root, storage) as usize,
+        };
+        if root.is_some() {
+            iter.pending.push_back(Pending::Tree((root, false)));
+        }
+        let len = iter.len;
+        iter.skip_front(beg);
+        iter.skip_back(len - end);
+        return iter;
+    }
+
+    /// returns the left child, the element and the right child of the node in curr
+    fn expand(
+        &self,
+        curr: &'a Link<T, U, NoAction, S>,
+        flip: bool,
+    ) -> Expanded<FlipTree<'a, T, U, S>, &'a T> {
+        let node = self.storage.get(curr.as_ref().unwrap());
+        let flip_children = flip ^ node.rev;
+        if flip {
+            return (
+                (&node.r, flip_children),
+                &node.value,
+                (&node.l, flip_children),
+            );
+        }
+        return (
+            (&node.l, flip_children),
+            &node.value,
+            (&node.r, flip_children),
+        );
+    }
+
+    fn push_front_expanded(&mut self, curr: &'a Link<T, U, NoAction, S>, flip: bool) {
+        let (l, value, r) = self.expand(curr, flip);
+        if r.0.is_some() {
+            self.pending.push_front(Pending::Tree(r));
+        }
+        self.pending.push_front(Pending::Value(value));
+        if l.0.is_some() {
+            self.pending.push_front(Pending::Tree(l));
+        }
+    }
+
+    fn push_back_expanded(&mut self, curr: &'a Link<T, U, NoAction, S>, flip: bool) {
+        let (l, value, r) = self.expand(curr, flip);
+        if l.0.is_some() {
+            self.pending.push_back(Pending::Tree(l));
+        }
+        self.pending.push_back(Pending::Value(value));
+        if r.0.is_some() {
+            self.pending.push_back(Pending::Tree(r));
+        }
+    }
+
+    /// skips the first cnt elements in O(log(N)) time, the whole subtrees with less elements are skipped at once
+    fn skip_front(&mut self, mut cnt: usize) {
+        self.len -= cnt;
+        while cnt > 0 {
+            match self.pending.pop_front() {
+                None => return,
+                Some(Pending::Value(_)) => cnt -= 1,
+                Some(Pending::Tree((curr, flip))) => {
+                    let curr_cnt = get_cnt(curr, self.storage) as usize;
+                    if curr_cnt <= cnt {
+                        cnt -= curr_cnt;
+                    } else {
+                        self.push_front_expanded(curr, flip);
+                    }
+                }
+            }
+        }
+    }
+
+    /// skips the last cnt elements in O(log(N)) time
+    fn skip_back(&mut self, mut cnt: usize) {
+        self.len -= cnt;
+        while cnt > 0 {
+            match self.pending.pop_back() {
+                None => return,
+                Some(Pending::Value(_)) => cnt -= 1,
+                Some(Pending::Tree((curr, flip))) => {
+                    let curr_cnt = get_cnt(curr, self.storage) as usize;
+                    if curr_cnt <= cnt {
+                        cnt -= curr_cnt;
+                    } else {
+                        self.push_back_expanded(curr, flip);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, U: 'a, S: Storage<T, U, NoAction>> Iterator for Iter<'a, T, U, S> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.pending.pop_front() {
+                None => return None,
+                Some(Pending::Value(value)) => {
+                    self.len -= 1;
+                    return Some(value);
+                }
+                Some(Pending::Tree((curr, flip))) => self.push_front_expanded(curr, flip),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, U: 'a, S: Storage<T, U, NoAction>> DoubleEndedIterator for Iter<'a, T, U, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.pending.pop_back() {
+                None => return None,
+                Some(Pending::Value(value)) => {
+                    self.len -= 1;
+                    return Some(value);
+                }
+                Some(Pending::Tree((curr, flip))) => self.push_back_expanded(curr, flip),
+            }
+        }
+    }
+}
+
+impl<'a, T, U: 'a, S: Storage<T, U, NoAction>> ExactSizeIterator for Iter<'a, T, U, S> {}
+
+/// iterator over mutable references to the elements, the tags of the nodes are pushed when the nodes are expanded so there are no pending reversals
+/// it is given to the function of Exray::iter_mut and cannot leave it, so the function values are recovered after the last change:
+/// exray.iter_mut(|values| values.for_each(|x| *x += 1));
+pub struct IterMut<'a, T, U, A: Action<T, U>, S: PointerStorage<T, U, A>> {
+    pending: VecDeque<PendingMut<'a, T, U, A, S>>,
+    storage: &'a mut S,
+    rev_functions: &'a [usize],
+    /// number of elements that are not visited yet
+    len: usize,
+}
+
+impl<'a, T, U: 'a, A: Action<T, U> + 'a, S: PointerStorage<T, U, A>> IterMut<'a, T, U, A, S> {
+    /// makes iterator over the elements with indices in [beg; end) of the treap root
+    pub(super) fn new(
+        root: &'a mut Link<T, U, A, S>,
+        storage: &'a mut S,
+        rev_functions: &'a [usize],
+        beg: usize,
+        end: usize,
+    ) -> Self {
+        let len = get_cnt(root, storage) as usize;
+        let mut iter = IterMut {
+            pending: VecDeque::new(),
+            storage: storage,
+            rev_functions: rev_functions,
+            len: len,
+        };
+        if root.is_some() {
+            iter.pending.push_back(Pending::Tree(root));
+        }
+        iter.skip_front(beg);
+        iter.skip_back(len - end);
+        return iter;
+    }
+
+    /// pushes the tag of the node in curr and splits it to the left child, the element and the right child
+    fn expand(
+        &mut self,
+        curr: &'a mut Link<T, U, A, S>,
+    ) -> Expanded<&'a mut Link<T, U, A, S>, &'a mut T> {
+        push(curr, self.rev_functions, self.storage);
+        let node = S::node_mut(curr.as_mut().unwrap());
+        return (&mut node.l, &mut node.value, &mut node.r);
+    }
+
+    fn push_front_expanded(&mut self, curr: &'a mut Link<T, U, A, S>) {
+        let (l, value, r) = self.expand(curr);
+        if r.is_some() {
+            self.pending.push_front(Pending::Tree(r));
+        }
+        self.pending.push_front(Pending::Value(value));
+        if l.is_some() {
+            self.pending.push_front(Pending::Tree(l));
+        }
+    }
+
+    fn push_back_expanded(&mut self, curr: &'a mut Link<T, U, A, S>) {
+        let (l, value, r) = self.expand(curr);
+        if l.is_some() {
+            self.pending.push_back(Pending::Tree(l));
+        }
+        self.pending.push_back(Pending::Value(value));
+        if r.is_some() {
+            self.pending.push_back(Pending::Tree(r));
+        }
+    }
+
+    fn skip_front(&mut self, mut cnt: usize) {
+        self.len -= cnt;
+        while cnt > 0 {
+            match self.pending.pop_front() {
+                None => return,
+                Some(Pending::Value(_)) => cnt -= 1,
+                Some(Pending::Tree(curr)) => {
+                    let curr_cnt = get_cnt(curr, self.storage) as usize;
+                    if curr_cnt <= cnt {
+                        cnt -= curr_cnt;
+                    } else {
+                        self.push_front_expanded(curr);
+                    }
+                }
+            }
+        }
+    }
+
+    fn skip_back(&mut self, mut cnt: usize) {
+        self.len -= cnt;
+        while cnt > 0 {
+            match self.pending.pop_back() {
+                None => return,
+                Some(Pending::Value(_)) => cnt -= 1,
+                Some(Pending::Tree(curr)) => {
+                    let curr_cnt = get_cnt(curr, self.storage) as usize;
+                    if curr_cnt <= cnt {
+                        cnt -= curr_cnt;
+                    } else {
+                        self.push_back_expanded(curr);
+                    }
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T, U: 'a, A: Action<T, U> + 'a, S: PointerStorage<T, U, A>> Iterator
+    for IterMut<'a, T, U, A, S>
+{
+    type Item = &'a mut T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.pending.pop_front() {
+                None => return None,
+                Some(Pending::Value(value)) => {
+                    self.len -= 1;
+                    return Some(value);
+                }
+                Some(Pending::Tree(curr)) => self.push_front_expanded(curr),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<'a, T, U: 'a, A: Action<T, U> + 'a, S: PointerStorage<T, U, A>> DoubleEndedIterator
+    for IterMut<'a, T, U, A, S>
+{
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.pending.pop_back() {
+                None => return None,
+                Some(Pending::Value(value)) => {
+                    self.len -= 1;
+                    return Some(value);
+                }
+                Some(Pending::Tree(curr)) => self.push_back_expanded(curr),
+            }
+        }
+    }
+}
+
+impl<'a, T, U: 'a, A: Action<T, U> + 'a, S: PointerStorage<T, U, A>> ExactSizeIterator
+    for IterMut<'a, T, U, A, S>
+{
+}
+
+/// RecoverGuard borrows the exray while the elements in the segment [beg; end) are changed through IterMut
+/// the function values of the segment are recovered when it is dropped, so they are recovered also when the changing function panics
+pub(super) struct RecoverGuard<'a, T, U, A, F, S>
+where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: PointerStorage<T, U, A>,
+{
+    exray: &'a mut Exray<T, U, A, F, S>,
+    beg: usize,
+    end: usize,
+}
+
+impl<'a, T, U, A, F, S> RecoverGuard<'a, T, U, A, F, S>
+where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: PointerStorage<T, U, A>,
+{
+    pub(super) fn new(exray: &'a mut Exray<T, U, A, F, S>, beg: usize, end: usize) -> Self {
+        RecoverGuard {
+            exray: exray,
+            beg: beg,
+            end: end,
+        }
+    }
+
+    /// returns iterator over the elements of the segment, the references cannot outlive the borrow of the guard
+    pub(super) fn values(&mut self) -> IterMut<'_, T, U, A, S> {
+        self.exray.values_mut(self.beg, self.end)
+    }
+}
+
+impl<'a, T, U, A, F, S> Drop for RecoverGuard<'a, T, U, A, F, S>
+where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: PointerStorage<T, U, A>,
+{
+    fn drop(&mut self) {
+        self.exray.recover_segment(self.beg, self.end);
+    }
+}
+
+/// iterator that takes the elements out of the treap, it owns the nodes that are not visited yet together with their storage
+pub struct IntoIter<T, U, A: Action<T, U>, S: Storage<T, U, A>> {
+    pending: VecDeque<Pending<T, Link<T, U, A, S>>>,
+    storage: S,
+    rev_functions: Vec<usize>,
+    /// number of elements that are not visited yet
+    len: usize,
+}
+
+impl<T, U, A: Action<T, U>, S: Storage<T, U, A>> IntoIter<T, U, A, S> {
+    pub(super) fn new(root: Link<T, U, A, S>, storage: S, rev_functions: Vec<usize>) -> Self {
+        let len = get_cnt(&root, &storage) as usize;
+        let mut iter = IntoIter {
+            pending: VecDeque::new(),
+            storage: storage,
+            rev_functions: rev_functions,
+            len: len,
+        };
+        if root.is_some() {
+            iter.pending.push_back(Pending::Tree(root));
+        }
+        return iter;
+    }
+
+    /// pushes the tag of the node in curr and takes it out of the storage
+    fn expand(&mut self, mut curr: Link<T, U, A, S>) -> Expanded<Link<T, U, A, S>, T> {
+        push(&mut curr, &self.rev_functions, &mut self.storage);
+        let node = self.storage.take(curr.unwrap());
+        return (node.l, node.value, node.r);
+    }
+}
+
+impl<T, U, A: Action<T, U>, S: Storage<T, U, A>> Iterator for IntoIter<T, U, A, S> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.pending.pop_front() {
+                None => return None,
+                Some(Pending::Value(value)) => {
+                    self.len -= 1;
+                    return Some(value);
+                }
+                Some(Pending::Tree(curr)) => {
+                    let (l, value, r) = self.expand(curr);
+                    if r.is_some() {
+                        self.pending.push_front(Pending::Tree(r));
+                    }
+                    if l.is_none() {
+                        self.len -= 1;
+                        return Some(value);
+                    }
+                    self.pending.push_front(Pending::Value(value));
+                    self.pending.push_front(Pending::Tree(l));
+                }
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T, U, A: Action<T, U>, S: Storage<T, U, A>> DoubleEndedIterator for IntoIter<T, U, A, S> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.pending.pop_back() {
+                None => return None,
+                Some(Pending::Value(value)) => {
+                    self.len -= 1;
+                    return Some(value);
+                }
+                Some(Pending::Tree(curr)) => {
+                    let (l, value, r) = self.expand(curr);
+                    if l.is_some() {
+                        self.pending.push_back(Pending::Tree(l));
+                    }
+                    if r.is_none() {
+                        self.len -= 1;
+                        return Some(value);
+                    }
+                    self.pending.push_back(Pending::Value(value));
+                    self.pending.push_back(Pending::Tree(r));
+                }
+            }
+        }
+    }
+}
+
+impl<T, U, A: Action<T, U>, S: Storage<T, U, A>> ExactSizeIterator for IntoIter<T, U, A, S> {}
+
+/// the subtrees that are not visited are dropped through the storage
+impl<T, U, A: Action<T, U>, S: Storage<T, U, A>> Drop for IntoIter<T, U, A, S> {
+    fn drop(&mut self) {
+        while let Some(part) = self.pending.pop_front() {
+            if let Pending::Tree(mut curr) = part {
+                drop_treap(&mut curr, &mut self.storage);
+            }
+        }
+    }
+}
@@ -1,5 +1,45 @@
+pub use self::aggregate::Aggregate;
+pub use self::aggregate::Avg;
+pub use self::aggregate::Bounded;
+pub use self::aggregate::FnAggregate;
+pub use self::aggregate::Func;
+pub use self::aggregate::Max;
+pub use self::aggregate::Min;
+pub use self::aggregate::Sum;
+pub use self::arena::ArenaStorage;
+pub use self::arena::InlineValues;
+pub use self::exray::ArenaExray;
 pub use self::exray::Exray;
 pub use self::exray::ExrayError;
-pub use self::treap::Func;
+pub use self::exray::PersistentExray;
+pub use self::guard::ElementMut;
+pub use self::inspect::ExrayStats;
+pub use self::inspect::InvariantError;
+pub use self::iter::IntoIter;
+pub use self::iter::Iter;
+pub use self::iter::IterMut;
+pub use self::registry::FunctionRegistry;
+#[cfg(feature = "serde")]
+pub use self::serialize::ExraySeed;
+pub use self::sorted::SortedExray;
+pub use self::treap::Action;
+pub use self::treap::BoxStorage;
+pub use self::treap::NoAction;
+pub use self::treap::PointerStorage;
+pub use self::treap::RcStorage;
+pub use self::treap::Storage;
+pub use self::treap::Values;
+pub use self::view::SegmentView;
+mod aggregate;
+mod arena;
 mod exray;
+mod guard;
+mod inspect;
+mod iter;
+mod registry;
+#[cfg(feature = "serde")]
+mod serialize;
+mod sorted;
+pub mod testing;
 mod treap;
+mod view;
@@ -0,0 +1,66 @@
+/// Here is the registry of the functions - the aggregates are registered under their ids, so they can be found by name and the exrays are compared by the ids of their functions
+use std::collections::HashMap;
+
+use crate::exray::aggregate::Aggregate;
+
+/// FunctionRegistry keeps functions by their ids, the id of an aggregate is stable across builds unlike the address of its code
+/// it is used for choosing the functions of new exrays by name and for binding the ids of read exrays to functions
+pub struct FunctionRegistry<F> {
+    functions: HashMap<String, F>,
+}
+
+impl<F> Default for FunctionRegistry<F> {
+    fn default() -> Self {
+        FunctionRegistry::<F> {
+            functions: HashMap::new(),
+        }
+    }
+}
+
+impl<F> FunctionRegistry<F> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// registers function under its id, the function which was registered before with the same id is replaced
+    pub fn register<T>(&mut self, function: F)
+    where
+        F: Aggregate<T>,
+    {
+        let id = function.id().into_owned();
+        self.functions.insert(id, function);
+    }
+
+    /// registers function also under alias, so it can be found by a short name, the exrays made with it still know it by its own id
+    pub fn register_as<T>(&mut self, alias: &str, function: F)
+    where
+        F: Aggregate<T> + Clone,
+    {
+        self.functions.insert(String::from(alias), function.clone());
+        self.register(function);
+    }
+
+    /// returns the function registered under id
+    pub fn get(&self, id: &str) -> Option<&F> {
+        self.functions.get(id)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.functions.contains_key(id)
+    }
+
+    /// returns the ids of the registered functions in sorted order
+    pub fn ids(&self) -> Vec<&str> {
+        let mut ids: Vec<&str> = self.functions.keys().map(|id| id.as_str()).collect();
+        ids.sort_unstable();
+        return ids;
+    }
+
+    pub fn len(&self) -> usize {
+        self.functions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.functions.is_empty()
+    }
+}
@@ -0,0 +1,159 @@
+/// Here is the serialization of the exray with serde, it is compiled only with the feature serde
+/// the exray is written as its elements in order and the ids of its functions, the functions are found again by their ids in FunctionRegistry when the exray is read
+use serde::de::{self, DeserializeSeed, MapAccess, SeqAccess, Visitor};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::exray::aggregate::Aggregate;
+use crate::exray::exray::Exray;
+use crate::exray::registry::FunctionRegistry;
+use crate::exray::treap::*;
+
+/// the seeds are made by the registry, because the read exrays take their functions from it
+impl<F> FunctionRegistry<F> {
+    /// returns seed for reading exray with some serde format, the functions of the exray are taken from the registry
+    /// for example with JSON: registry.exray_seed().deserialize(&mut serde_json::Deserializer::from_str(text))
+    pub fn exray_seed<T, U, A, S>(&self) -> ExraySeed<'_, T, U, A, F, S> {
+        ExraySeed {
+            registry: self,
+            phantom: PhantomData,
+        }
+    }
+}
+
+/// the elements are serialized as a sequence from the iterator, so they are not collected in Vector first
+struct Elements<'a, T, U, F, S: Storage<T, U, NoAction>>(&'a Exray<T, U, NoAction, F, S>);
+
+impl<'a, T, U, F, S> Serialize for Elements<'a, T, U, F, S>
+where
+    T: Serialize,
+    S: Storage<T, U, NoAction>,
+{
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        serializer.collect_seq(self.0.iter())
+    }
+}
+
+/// like Index it is only for exrays without actions: serialize gets &self, so the pending tags cannot be pushed to the elements and the stored elements can be older than the real ones
+/// the elements of exray with actions can be written after iter_mut pushes the tags, for example by serializing them as a sequence
+impl<T, U, F, S> Serialize for Exray<T, U, NoAction, F, S>
+where
+    T: Serialize,
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, NoAction>,
+{
+    fn serialize<Z: Serializer>(&self, serializer: Z) -> Result<Z::Ok, Z::Error> {
+        let mut state = serializer.serialize_struct("Exray", 2)?;
+        state.serialize_field("functions", self.function_ids())?;
+        state.serialize_field("elements", &Elements(self))?;
+        return state.end();
+    }
+}
+
+/// ExraySeed reads exray and binds the ids of its functions to the functions in the registry, unknown id is an error of the format
+pub struct ExraySeed<'a, T, U, A, F, S> {
+    registry: &'a FunctionRegistry<F>,
+    phantom: PhantomData<fn() -> (T, U, A, S)>,
+}
+
+#[derive(Deserialize)]
+#[serde(field_identifier, rename_all = "lowercase")]
+enum Field {
+    Functions,
+    Elements,
+}
+
+impl<'a, T, U, A, F, S> ExraySeed<'a, T, U, A, F, S>
+where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U> + Clone,
+    S: Storage<T, U, A>,
+{
+    fn build<E: de::Error>(
+        &self,
+        ids: Vec<String>,
+        elements: Vec<T>,
+    ) -> Result<Exray<T, U, A, F, S>, E> {
+        let mut functions = Vec::<F>::new();
+        for id in ids {
+            match self.registry.get(&id) {
+                None => return Err(E::custom(format!("unknown function `{}`", id))),
+                Some(function) => functions.push(function.clone()),
+            }
+        }
+        return Ok(Exray::new(elements, functions));
+    }
+}
+
+impl<'de, 'a, T, U, A, F, S> DeserializeSeed<'de> for ExraySeed<'a, T, U, A, F, S>
+where
+    T: Deserialize<'de>,
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U> + Clone,
+    S: Storage<T, U, A>,
+{
+    type Value = Exray<T, U, A, F, S>;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<Self::Value, D::Error> {
+        deserializer.deserialize_struct("Exray", &["functions", "elements"], self)
+    }
+}
+
+impl<'de, 'a, T, U, A, F, S> Visitor<'de> for ExraySeed<'a, T, U, A, F, S>
+where
+    T: Deserialize<'de>,
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U> + Clone,
+    S: Storage<T, U, A>,
+{
+    type Value = Exray<T, U, A, F, S>;
+
+    fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "exray with functions and elements")
+    }
+
+    /// the compact formats like bincode write the fields in order without their names
+    fn visit_seq<V: SeqAccess<'de>>(self, mut seq: V) -> Result<Self::Value, V::Error> {
+        let ids = match seq.next_element::<Vec<String>>()? {
+            None => return Err(de::Error::invalid_length(0, &self)),
+            Some(ids) => ids,
+        };
+        let elements = match seq.next_element::<Vec<T>>()? {
+            None => return Err(de::Error::invalid_length(1, &self)),
+            Some(elements) => elements,
+        };
+        return self.build(ids, elements);
+    }
+
+    fn visit_map<V: MapAccess<'de>>(self, mut map: V) -> Result<Self::Value, V::Error> {
+        let mut ids = None;
+        let mut elements = None;
+        while let Some(key) = map.next_key::<Field>()? {
+            match key {
+                Field::Functions => {
+                    if ids.is_some() {
+                        return Err(de::Error::duplicate_field("functions"));
+                    }
+                    ids = Some(map.next_value::<Vec<String>>()?);
+                }
+                Field::Elements => {
+                    if elements.is_some() {
+                        return Err(de::Error::duplicate_field("elements"));
+                    }
+                    elements = Some(map.next_value::<Vec<T>>()?);
+                }
+            }
+        }
+        let ids = match ids {
+            None => return Err(de::Error::missing_field("functions")),
+            Some(ids) => ids,
+        };
+        let elements = match elements {
+            None => return Err(de::Error::missing_field("elements")),
+            Some(elements) => elements,
+        };
+        return self.build(ids, elements);
+    }
+}
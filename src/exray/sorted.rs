@@ -0,0 +1,140 @@
+/// Here is the sorted mode of the exray - the elements are kept in the order of a comparator and they are found by binary search in the treap
+use crate::exray::aggregate::*;
+use crate::exray::exray::*;
+use crate::exray::iter::*;
+use crate::exray::treap::*;
+use std::cmp;
+use std::cmp::Ordering;
+
+/// SortedExray keeps its elements sorted by the comparator cmp, the equal elements are in the order of their insertion
+/// the functions are maintained like in Exray, so the values for the elements between two keys can be found in O(log(N)) time
+/// there are no actions and reversals, because they can break the order
+pub struct SortedExray<T, U, F = Func<T, U>, S = BoxStorage, C = fn(&T, &T) -> Ordering>
+where
+    S: Storage<T, U, NoAction>,
+{
+    /// the exray with the elements in sorted order
+    exray: Exray<T, U, NoAction, F, S>,
+    /// the comparator for the elements
+    cmp: C,
+}
+
+impl<T: Ord, U, F: Aggregate<T, Value = U>, S: Storage<T, U, NoAction>> SortedExray<T, U, F, S> {
+    /// function for making sorted exray with the elements in their natural order, they are sorted in O(N*log(N)) time
+    pub fn new(elements: Vec<T>, functions: Vec<F>) -> Self {
+        return Self::with_comparator(elements, functions, Ord::cmp);
+    }
+}
+
+impl<T, U, F, S, C> SortedExray<T, U, F, S, C>
+where
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, NoAction>,
+    C: Fn(&T, &T) -> Ordering,
+{
+    /// function for making sorted exray with the elements in the order of cmp, the sorting is stable
+    pub fn with_comparator(mut elements: Vec<T>, functions: Vec<F>, cmp: C) -> Self {
+        elements.sort_by(|a, b| cmp(a, b));
+        return SortedExray::<T, U, F, S, C> {
+            exray: Exray::new(elements, functions),
+            cmp: cmp,
+        };
+    }
+
+    pub fn len(&self) -> usize {
+        self.exray.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.exray.is_empty()
+    }
+
+    /// returns the exray with the elements in sorted order, all of its queries can be used
+    pub fn as_exray(&self) -> &Exray<T, U, NoAction, F, S> {
+        &self.exray
+    }
+
+    /// returns the index of the first element which is not less than key
+    pub fn lower_bound(&self, key: &T) -> usize {
+        let cmp = &self.cmp;
+        return self
+            .exray
+            .partition_point(|value| cmp(value, key) == Ordering::Less);
+    }
+
+    /// returns the index of the first element which is greater than key
+    pub fn upper_bound(&self, key: &T) -> usize {
+        let cmp = &self.cmp;
+        return self
+            .exray
+            .partition_point(|value| cmp(value, key) != Ordering::Greater);
+    }
+
+    /// returns the number of elements which are less than key
+    pub fn rank(&self, key: &T) -> usize {
+        return self.lower_bound(key);
+    }
+
+    /// returns the element with index ind in the sorted order
+    pub fn select(&self, ind: usize) -> Option<&T> {
+        if self.len() <= ind {
+            return None;
+        }
+        return Some(&self.exray[ind]);
+    }
+
+    /// inserts value after the elements which are equal to it and returns its index
+    pub fn insert(&mut self, value: T) -> usize {
+        let ind = self.upper_bound(&value);
+        // the index is at most the size, so the insertion can't fail
+        self.exray.insert(ind, value).unwrap();
+        return ind;
+    }
+
+    /// erases the first element which is equal to key, returns false if there is no such element
+    pub fn remove(&mut self, key: &T) -> bool {
+        let ind = self.lower_bound(key);
+        if ind == self.len() || (self.cmp)(&self.exray[ind], key) != Ordering::Equal {
+            return false;
+        }
+        self.exray.erase(ind).unwrap();
+        return true;
+    }
+
+    /// returns the functions values for the elements which are not less than from and less than to, for empty segment the identities of the functions are returned
+    pub fn functions_values_between(&self, from: &T, to: &T) -> Vec<U>
+    where
+        U: Clone,
+    {
+        let beg = self.lower_bound(from);
+        let end = cmp::max(beg, self.lower_bound(to));
+        return self.exray.range_functions_values(beg..end).unwrap();
+    }
+
+    /// returns slice to functions values for all elements
+    pub fn functions_values(&self) -> &[U] {
+        self.exray.functions_values()
+    }
+
+    /// moves the elements which are not less than key to new sorted exray in O(log(N)) time
+    pub fn split_by_key(&mut self, key: &T) -> Self
+    where
+        F: Clone,
+        C: Clone,
+    {
+        let ind = self.lower_bound(key);
+        return SortedExray::<T, U, F, S, C> {
+            exray: self.exray.extract_range(ind..).unwrap(),
+            cmp: self.cmp.clone(),
+        };
+    }
+
+    pub fn to_vec(&self) -> Vec<&T> {
+        self.exray.to_vec()
+    }
+
+    /// returns iterator over the elements in sorted order
+    pub fn iter(&self) -> Iter<'_, T, U, S> {
+        self.exray.iter()
+    }
+}
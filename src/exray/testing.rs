@@ -0,0 +1,546 @@
+/// Here is the differential tester of the exray - random sequences of operations are run on the exray and on a Vector with naive computation of the functions, and their results are compared
+/// it can be used for checking new aggregates, actions and storages, the failing sequences are minimized so they can be replayed and debugged
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::fmt;
+use std::fmt::Debug;
+use std::marker::PhantomData;
+use std::panic;
+use std::panic::AssertUnwindSafe;
+
+use crate::exray::aggregate::Aggregate;
+use crate::exray::exray::{Exray, ExrayError};
+use crate::exray::treap::*;
+
+/// the operations of the tester, the ranges are [beg; end) like in the range functions of the exray
+/// besides the tested exray there is a second one - the buffer, which gets the extracted and cloned segments and is inserted back with InsertExray
+/// the indices are not checked when the operations are made, so for the invalid ones the exray should return the same error as the model
+/// A is the action of the exray, the updates are made only when the test has generator of actions
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operation<T, A = NoAction> {
+    Insert {
+        index: usize,
+        value: T,
+    },
+    Erase {
+        index: usize,
+    },
+    /// moves the range to the buffer, the old elements of the buffer are dropped
+    Extract {
+        beg: usize,
+        end: usize,
+    },
+    /// clones the range to the buffer, the old elements of the buffer are dropped
+    Clone {
+        beg: usize,
+        end: usize,
+    },
+    /// moves the whole buffer into the exray at index
+    InsertExray {
+        index: usize,
+    },
+    /// compares the functions values for the range with the naive ones
+    Query {
+        beg: usize,
+        end: usize,
+    },
+    /// applies the action to the range, the model applies it to every element
+    Update {
+        beg: usize,
+        end: usize,
+        action: A,
+    },
+    /// reverses the range
+    Reverse {
+        beg: usize,
+        end: usize,
+    },
+}
+
+/// the failing sequence of operations, step is the index of the operation after which the exray differs from the model
+/// step is None if the exray is wrong right after it is built from initial
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure<T, A = NoAction> {
+    pub seed: u64,
+    pub initial: Vec<T>,
+    pub operations: Vec<Operation<T, A>>,
+    pub step: Option<usize>,
+    pub message: String,
+}
+
+impl<T: Debug, A: Debug> fmt::Display for Failure<T, A> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut text = match self.step {
+            None => format!("the built exray failed: {}\n", self.message),
+            Some(step) => format!("operation {} failed: {}\n", step, self.message),
+        };
+        text.push_str(&format!(
+            "seed: {}\ninitial: {:?}\noperations:\n",
+            self.seed, self.initial
+        ));
+        for (i, operation) in self.operations.iter().enumerate() {
+            text.push_str(&format!("    {}: {:?}\n", i, operation));
+        }
+        write!(f, "{}", text)
+    }
+}
+
+/// computes the functions values for elements by combining the leaf values one by one from the left, this is the model for the values of the exray
+pub fn naive_values<T, U, F: Aggregate<T, Value = U>>(elements: &[T], functions: &[F]) -> Vec<U> {
+    let mut values = Vec::<U>::new();
+    for function in functions {
+        let mut value = function.identity();
+        for (cnt, element) in elements.iter().enumerate() {
+            let leaf = function.leaf(element);
+            value = if cnt == 0 {
+                leaf
+            } else {
+                function.combine((&value, cnt as u64), (&leaf, 1))
+            };
+        }
+        values.push(value);
+    }
+    return values;
+}
+
+/// returns the error that the range functions of the exray should return for range [beg; end) of exray with len elements
+fn range_error(beg: usize, end: usize, len: usize) -> Option<ExrayError> {
+    if end < beg {
+        return Some(ExrayError::InvalidRange { beg: beg, end: end });
+    }
+    if len < end {
+        return Some(ExrayError::IndexOutOfBounds {
+            index: end,
+            len: len,
+        });
+    }
+    return None;
+}
+
+/// compares the result of an operation of the exray with the expected error of the model
+fn compare_error<V>(
+    result: &Result<V, ExrayError>,
+    expected: &Option<ExrayError>,
+) -> Result<(), String> {
+    return match (result, expected) {
+        (Ok(_), None) => Ok(()),
+        (Err(e), Some(expected)) if e == expected => Ok(()),
+        (Err(e), None) => Err(format!("unexpected error: {}", e)),
+        (Ok(_), Some(expected)) => Err(format!("expected error: {}", expected)),
+        (Err(e), Some(expected)) => Err(format!("expected error: {}, got: {}", expected, e)),
+    };
+}
+
+/// compares the elements, the functions values and the invariants of exray with the model
+/// the elements are read with get_element, because it pushes the pending tags on the way to them
+fn compare_exray<T, U, A, F, S>(
+    name: &str,
+    exray: &mut Exray<T, U, A, F, S>,
+    model: &[T],
+    functions: &[F],
+) -> Result<(), String>
+where
+    T: Clone + Debug + PartialEq,
+    U: Clone + Debug + PartialEq,
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, A>,
+{
+    if exray.len() != model.len() {
+        return Err(format!(
+            "the {} has {} elements, expected {}",
+            name,
+            exray.len(),
+            model.len()
+        ));
+    }
+    let mut elements = Vec::<T>::new();
+    for i in 0..model.len() {
+        // the index is in bounds, because the lengths are equal
+        elements.push(exray.get_element(i).unwrap().clone());
+    }
+    if elements != model {
+        return Err(format!(
+            "the {} has elements {:?}, expected {:?}",
+            name, elements, model
+        ));
+    }
+    // functions_values gives empty slice for empty exray, while the queries give the identities
+    let expected = if model.is_empty() {
+        Vec::<U>::new()
+    } else {
+        naive_values(model, functions)
+    };
+    if exray.functions_values() != &expected[..] {
+        return Err(format!(
+            "the {} has functions values {:?}, expected {:?}",
+            name,
+            exray.functions_values(),
+            expected
+        ));
+    }
+    return match exray.validate() {
+        Err(e) => Err(format!("the {} is broken: {}", name, e)),
+        Ok(_) => Ok(()),
+    };
+}
+
+/// returns the message of a caught panic
+fn panic_message(payload: Box<dyn std::any::Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        return format!("panic: {}", message);
+    }
+    if let Some(message) = payload.downcast_ref::<String>() {
+        return format!("panic: {}", message);
+    }
+    return String::from("panic");
+}
+
+/// removes chunks of items while fails is still true without them, the chunks are halved down to single items
+/// returns true if something was removed
+fn shrink<X: Clone, C: FnMut(&[X]) -> bool>(items: &mut Vec<X>, mut fails: C) -> bool {
+    let mut removed = false;
+    let mut chunk = items.len() / 2;
+    if chunk == 0 {
+        chunk = 1;
+    }
+    loop {
+        let mut start = 0;
+        while start < items.len() {
+            let end = if items.len() < start + chunk {
+                items.len()
+            } else {
+                start + chunk
+            };
+            let mut candidate = items[..start].to_vec();
+            candidate.extend_from_slice(&items[end..]);
+            if fails(&candidate) {
+                *items = candidate;
+                removed = true;
+            } else {
+                start = end;
+            }
+        }
+        if chunk == 1 {
+            break;
+        }
+        chunk /= 2;
+    }
+    return removed;
+}
+
+/// DifferentialTest runs random sequences of operations on exrays with the functions and compares them with the model after every operation
+/// the elements are made by generate, so it works for any type of elements and any functions
+/// for example: DifferentialTest::new(functions, |rng| rng.gen_range(-100, 100)).operations(200).run::<BoxStorage>()
+/// the exrays have no actions unless generator of actions is given with actions, then the lazy updates are tested together with the reversals
+pub struct DifferentialTest<T, F, G, A = NoAction, H = fn(&mut StdRng) -> NoAction> {
+    functions: Vec<F>,
+    generate: G,
+    generate_action: Option<H>,
+    seed: u64,
+    runs: usize,
+    initial_len: usize,
+    operations: usize,
+    marker: PhantomData<fn() -> (T, A)>,
+}
+
+impl<T, F, G> DifferentialTest<T, F, G> {
+    /// by default there are 10 runs with 100 operations on exrays with 16 initial elements
+    pub fn new(functions: Vec<F>, generate: G) -> Self {
+        DifferentialTest::<T, F, G> {
+            functions: functions,
+            generate: generate,
+            generate_action: None,
+            seed: 0,
+            runs: 10,
+            initial_len: 16,
+            operations: 100,
+            marker: PhantomData,
+        }
+    }
+
+    /// makes the test for exrays with action A, the actions of the updates are made by generate_action
+    pub fn actions<A, H: Fn(&mut StdRng) -> A>(
+        self,
+        generate_action: H,
+    ) -> DifferentialTest<T, F, G, A, H> {
+        DifferentialTest::<T, F, G, A, H> {
+            functions: self.functions,
+            generate: self.generate,
+            generate_action: Some(generate_action),
+            seed: self.seed,
+            runs: self.runs,
+            initial_len: self.initial_len,
+            operations: self.operations,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T, U, A, F, G, H> DifferentialTest<T, F, G, A, H>
+where
+    T: Clone + Debug + PartialEq,
+    U: Clone + Debug + PartialEq,
+    A: Action<T, U> + Debug + PartialEq,
+    F: Aggregate<T, Value = U> + Clone,
+    G: Fn(&mut StdRng) -> T,
+    H: Fn(&mut StdRng) -> A,
+{
+    /// sets the seed of the first run, the next runs use the next seeds
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        return self;
+    }
+
+    pub fn runs(mut self, runs: usize) -> Self {
+        self.runs = runs;
+        return self;
+    }
+
+    pub fn initial_len(mut self, initial_len: usize) -> Self {
+        self.initial_len = initial_len;
+        return self;
+    }
+
+    pub fn operations(mut self, operations: usize) -> Self {
+        self.operations = operations;
+        return self;
+    }
+
+    /// makes random initial elements and sequence of operations for seed, the operations are mostly valid for the lengths of the exray and the buffer
+    pub fn generate(&self, seed: u64) -> (Vec<T>, Vec<Operation<T, A>>) {
+        let mut rng = StdRng::seed_from_u64(seed);
+        let initial: Vec<T> = (0..self.initial_len)
+            .map(|_| (self.generate)(&mut rng))
+            .collect();
+        let mut len = initial.len();
+        let mut buffer_len = 0;
+        let mut operations = Vec::<Operation<T, A>>::new();
+        for _ in 0..self.operations {
+            // the bounds are one more than the valid ones, so sometimes the errors are checked too
+            let beg = rng.gen_range(0, len + 2);
+            let end = rng.gen_range(beg, len + 2);
+            let operation = match rng.gen_range(0, 12) {
+                0..=2 => Operation::Insert {
+                    index: rng.gen_range(0, len + 2),
+                    value: (self.generate)(&mut rng),
+                },
+                3 | 4 => Operation::Erase {
+                    index: rng.gen_range(0, len + 1),
+                },
+                5 => Operation::Extract { beg: beg, end: end },
+                6 => Operation::Clone { beg: beg, end: end },
+                7 => Operation::InsertExray {
+                    index: rng.gen_range(0, len + 2),
+                },
+                8 => Operation::Reverse { beg: beg, end: end },
+                9 => match &self.generate_action {
+                    None => Operation::Query { beg: beg, end: end },
+                    Some(generate_action) => Operation::Update {
+                        beg: beg,
+                        end: end,
+                        action: generate_action(&mut rng),
+                    },
+                },
+                _ => Operation::Query { beg: beg, end: end },
+            };
+            match operation {
+                Operation::Insert { index, .. } if index <= len => len += 1,
+                Operation::Erase { index } if index < len => len -= 1,
+                Operation::Extract { beg, end } if end <= len => {
+                    len -= end - beg;
+                    buffer_len = end - beg;
+                }
+                Operation::Clone { beg, end } if end <= len => buffer_len = end - beg,
+                Operation::InsertExray { index } if index <= len => {
+                    len += buffer_len;
+                    buffer_len = 0;
+                }
+                _ => {}
+            }
+            operations.push(operation);
+        }
+        return (initial, operations);
+    }
+
+    /// runs the operations on exray with storage S built from initial and compares it with the model after every operation
+    /// the panics of the exray are caught and returned as failures too
+    pub fn check<S: Storage<T, U, A>>(
+        &self,
+        seed: u64,
+        initial: &[T],
+        operations: &[Operation<T, A>],
+    ) -> Result<(), Failure<T, A>> {
+        let mut step = None;
+        let result = panic::catch_unwind(AssertUnwindSafe(|| {
+            return self.replay::<S>(seed, initial, operations, &mut step);
+        }));
+        let message = match result {
+            Ok(Ok(())) => return Ok(()),
+            Ok(Err(message)) => message,
+            Err(payload) => panic_message(payload),
+        };
+        return Err(Failure {
+            seed: seed,
+            initial: initial.to_vec(),
+            operations: operations.to_vec(),
+            step: step,
+            message: message,
+        });
+    }
+
+    /// step is the index of the current operation, so it is known when the exray panics
+    fn replay<S: Storage<T, U, A>>(
+        &self,
+        seed: u64,
+        initial: &[T],
+        operations: &[Operation<T, A>],
+        step: &mut Option<usize>,
+    ) -> Result<(), String> {
+        let functions = &self.functions[..];
+        let mut exray =
+            Exray::<T, U, A, F, S>::with_rng(initial.to_vec(), functions.to_vec(), seed);
+        let mut buffer = Exray::<T, U, A, F, S>::with_rng(Vec::new(), functions.to_vec(), seed);
+        let mut model = initial.to_vec();
+        let mut buffer_model = Vec::<T>::new();
+        compare_exray("exray", &mut exray, &model, functions)?;
+
+        for (i, operation) in operations.iter().enumerate() {
+            *step = Some(i);
+            let len = model.len();
+            match operation {
+                Operation::Insert { index, value } => {
+                    let expected = if len < *index {
+                        Some(ExrayError::IndexOutOfBounds {
+                            index: *index,
+                            len: len,
+                        })
+                    } else {
+                        None
+                    };
+                    let result = exray.insert(*index, value.clone());
+                    compare_error(&result, &expected)?;
+                    if expected.is_none() {
+                        model.insert(*index, value.clone());
+                    }
+                }
+                Operation::Erase { index } => {
+                    let expected = if len <= *index {
+                        Some(ExrayError::IndexOutOfBounds {
+                            index: *index,
+                            len: len,
+                        })
+                    } else {
+                        None
+                    };
+                    let result = exray.erase(*index);
+                    compare_error(&result, &expected)?;
+                    if expected.is_none() {
+                        model.remove(*index);
+                    }
+                }
+                Operation::Extract { beg, end } | Operation::Clone { beg, end } => {
+                    let expected = range_error(*beg, *end, len);
+                    let result = match operation {
+                        Operation::Extract { .. } => exray.extract_range(*beg..*end),
+                        _ => exray.clone_range(*beg..*end),
+                    };
+                    compare_error(&result, &expected)?;
+                    if let Ok(segment) = result {
+                        buffer = segment;
+                        buffer_model = match operation {
+                            Operation::Extract { .. } => model.drain(*beg..*end).collect(),
+                            _ => model[*beg..*end].to_vec(),
+                        };
+                    }
+                }
+                Operation::InsertExray { index } => {
+                    let expected = if len < *index {
+                        Some(ExrayError::IndexOutOfBounds {
+                            index: *index,
+                            len: len,
+                        })
+                    } else {
+                        None
+                    };
+                    let result = exray.insert_exray(&mut buffer, *index);
+                    compare_error(&result, &expected)?;
+                    if expected.is_none() {
+                        let tail = model.split_off(*index);
+                        model.append(&mut buffer_model);
+                        model.extend(tail);
+                    }
+                }
+                Operation::Query { beg, end } => {
+                    let expected = range_error(*beg, *end, len);
+                    let result = exray.range_functions_values(*beg..*end);
+                    compare_error(&result, &expected)?;
+                    if let Ok(values) = result {
+                        let naive = naive_values(&model[*beg..*end], functions);
+                        if values != naive {
+                            return Err(format!(
+                                "the query has values {:?}, expected {:?}",
+                                values, naive
+                            ));
+                        }
+                    }
+                }
+                Operation::Update { beg, end, action } => {
+                    let expected = range_error(*beg, *end, len);
+                    let result = exray.update_range(*beg..*end, action.clone());
+                    compare_error(&result, &expected)?;
+                    if expected.is_none() {
+                        for element in model[*beg..*end].iter_mut() {
+                            action.apply_value(element);
+                        }
+                    }
+                }
+                Operation::Reverse { beg, end } => {
+                    let expected = range_error(*beg, *end, len);
+                    let result = exray.reverse_range(*beg..*end);
+                    compare_error(&result, &expected)?;
+                    if expected.is_none() {
+                        model[*beg..*end].reverse();
+                    }
+                }
+            }
+            compare_exray("exray", &mut exray, &model, functions)?;
+            compare_exray("buffer", &mut buffer, &buffer_model, functions)?;
+        }
+        return Ok(());
+    }
+
+    /// removes operations and initial elements from the failing sequence while it still fails
+    /// the treap is built with the same seed, so the shape of the treap for the same elements doesn't change while the sequence is minimized
+    /// the panics of the candidates are caught by check and returned as failures
+    pub fn minimize<S: Storage<T, U, A>>(&self, failure: Failure<T, A>) -> Failure<T, A> {
+        let seed = failure.seed;
+        let mut initial = failure.initial;
+        let mut operations = failure.operations;
+        loop {
+            let removed_operations = shrink(&mut operations, |candidate| {
+                self.check::<S>(seed, &initial, candidate).is_err()
+            });
+            let removed_elements = shrink(&mut initial, |candidate| {
+                self.check::<S>(seed, candidate, &operations).is_err()
+            });
+            if !removed_operations && !removed_elements {
+                break;
+            }
+        }
+        // the minimized sequence still fails, so this returns its failure
+        return self.check::<S>(seed, &initial, &operations).unwrap_err();
+    }
+
+    /// runs the random sequences with storage S and returns the first failure minimized
+    pub fn run<S: Storage<T, U, A>>(&self) -> Result<(), Failure<T, A>> {
+        for seed in self.seed..self.seed + self.runs as u64 {
+            let (initial, operations) = self.generate(seed);
+            match self.check::<S>(seed, &initial, &operations) {
+                Ok(_) => {}
+                Err(failure) => return Err(self.minimize::<S>(failure)),
+            }
+        }
+        return Ok(());
+    }
+}
@@ -1,262 +1,1398 @@
 /// Here is the "inner" part of the exray - the structure implicit treap and functions controlling it
+use crate::exray::aggregate::Aggregate;
+use std::cmp;
+use std::ops::DerefMut;
+use std::rc::Rc;
 /// this type is the pointer to an implicit treap
-pub type Link<T, U> = Option<Box<ImplicitTreap<T, U>>>;
-/// this type is the functions that the treap maintains, the function should expect the following:
-/// first parameter which is an option and should contain information for left part of the segment - reference to the value of the maintained function for the left part and number of elements  
-/// second parameter is reference to the value in the middle i.e.
-/// third parameter which is an option and analogous to the first only the information is for the right part of the segment
-pub type Func<T, U> = fn(Option<(&U, u64)>, &T, Option<(&U, u64)>) -> U;
+pub type Link<T, U, A, S> = Option<<S as Storage<T, U, A>>::Ptr>;
+/// this trait is for the actions that can be applied lazily over a whole segment of the treap
+/// the action should know how to change a single element, how to change the values of the maintained functions for a segment and how to be composed with a newer action
+pub trait Action<T, U>: Clone {
+    /// applies the action to a single element
+    fn apply_value(&self, value: &mut T);
+    /// changes fvalue, which is the value of the maintained function with index ind for a segment of cnt elements, to the value after the action is applied to all of the elements
+    /// ind is the position of the function in the exray, so it changes for the next functions when a function is added or removed
+    fn apply_fvalue(&self, ind: usize, fvalue: &mut U, cnt: u64);
+    /// composes the action with newer one, after that self should do the same as applying the old self and then newer
+    fn compose(&mut self, newer: &Self);
+}
+/// this is the default action for treaps without range updates, it has no values so the tags in the nodes take no memory
+#[derive(Debug, Clone, PartialEq)]
+pub enum NoAction {}
+impl<T, U> Action<T, U> for NoAction {
+    fn apply_value(&self, _value: &mut T) {
+        match *self {}
+    }
+    fn apply_fvalue(&self, _ind: usize, _fvalue: &mut U, _cnt: u64) {
+        match *self {}
+    }
+    fn compose(&mut self, _newer: &Self) {
+        match *self {}
+    }
+}
+/// this trait is for the ways the nodes of the treap are stored, the functions for the treap reach the nodes only through the storage
+pub trait Storage<T, U, A>: Default + Sized {
+    /// the pointer to a node of the treap, the tree owns its nodes through these pointers
+    type Ptr;
+    /// the container for the functions' values in the nodes
+    type Values: Values<U>;
+
+    fn alloc(&mut self, node: ImplicitTreap<T, U, A, Self>) -> Self::Ptr;
+    fn get<'a>(&'a self, ptr: &'a Self::Ptr) -> &'a ImplicitTreap<T, U, A, Self>;
+    /// if the node is shared with other trees it is copied first, so the other trees are not changed
+    fn get_mut<'a>(&'a mut self, ptr: &'a mut Self::Ptr) -> &'a mut ImplicitTreap<T, U, A, Self>;
+    /// returns the node at the end of path from ptr, where false in path is for the left child and true is for the right child
+    fn get_mut_path<'a>(
+        &'a mut self,
+        ptr: &'a mut Self::Ptr,
+        path: &[bool],
+    ) -> &'a mut ImplicitTreap<T, U, A, Self>;
+    /// frees the pointer and returns its node if it was not shared with other trees
+    fn free(&mut self, ptr: Self::Ptr) -> Option<ImplicitTreap<T, U, A, Self>>;
+    /// frees the pointer and returns its node, if the node was shared with other trees a copy of it is returned
+    fn take(&mut self, ptr: Self::Ptr) -> ImplicitTreap<T, U, A, Self>;
+
+    /// moves the tree in curr from the storage other to this one, by default the pointers own their nodes so they are just moved in O(1) time
+    /// storages that own their nodes, like ArenaStorage, copy every node of the tree, so for them this is O(M) for M nodes
+    fn move_tree(&mut self, curr: Link<T, U, A, Self>, _other: &mut Self) -> Link<T, U, A, Self> {
+        return curr;
+    }
+
+    /// makes a copy of the tree in curr with new storage, by default all nodes are copied
+    fn clone_tree(&self, curr: &Link<T, U, A, Self>) -> (Self, Link<T, U, A, Self>)
+    where
+        T: Clone,
+        U: Clone,
+        A: Clone,
+    {
+        let mut storage = Self::default();
+        let root = clone_treap(curr, self, &mut storage);
+        return (storage, root);
+    }
+}
+
+/// this is the default storage - every node is in its own Box
+#[derive(Default, Clone, Copy)]
+pub struct BoxStorage;
+
+impl<T, U, A> Storage<T, U, A> for BoxStorage {
+    type Ptr = Box<ImplicitTreap<T, U, A, BoxStorage>>;
+    type Values = Vec<U>;
+
+    fn alloc(&mut self, node: ImplicitTreap<T, U, A, Self>) -> Self::Ptr {
+        Box::new(node)
+    }
+    fn get<'a>(&'a self, ptr: &'a Self::Ptr) -> &'a ImplicitTreap<T, U, A, Self> {
+        ptr
+    }
+    fn get_mut<'a>(&'a mut self, ptr: &'a mut Self::Ptr) -> &'a mut ImplicitTreap<T, U, A, Self> {
+        ptr
+    }
+    fn get_mut_path<'a>(
+        &'a mut self,
+        ptr: &'a mut Self::Ptr,
+        path: &[bool],
+    ) -> &'a mut ImplicitTreap<T, U, A, Self> {
+        let mut node = &mut **ptr;
+        for &right in path {
+            let next = if right { &mut node.r } else { &mut node.l };
+            node = &mut **next.as_mut().unwrap();
+        }
+        return node;
+    }
+    fn free(&mut self, ptr: Self::Ptr) -> Option<ImplicitTreap<T, U, A, Self>> {
+        Some(*ptr)
+    }
+    fn take(&mut self, ptr: Self::Ptr) -> ImplicitTreap<T, U, A, Self> {
+        *ptr
+    }
+}
+
+impl<T, U, A> PointerStorage<T, U, A> for BoxStorage {
+    fn node_mut(ptr: &mut Self::Ptr) -> &mut ImplicitTreap<T, U, A, Self> {
+        ptr
+    }
+}
+
+/// this is the storage for persistent treaps - the nodes are shared between versions through Rc and they are copied only when they are changed
+/// in this way every change copies only the O(log(N)) nodes on its paths and copying whole tree is O(1)
+#[derive(Default, Clone, Copy)]
+pub struct RcStorage;
+
+impl<T: Clone, U: Clone, A: Clone> Storage<T, U, A> for RcStorage {
+    type Ptr = Rc<ImplicitTreap<T, U, A, RcStorage>>;
+    type Values = Vec<U>;
+
+    fn alloc(&mut self, node: ImplicitTreap<T, U, A, Self>) -> Self::Ptr {
+        Rc::new(node)
+    }
+    fn get<'a>(&'a self, ptr: &'a Self::Ptr) -> &'a ImplicitTreap<T, U, A, Self> {
+        ptr
+    }
+    fn get_mut<'a>(&'a mut self, ptr: &'a mut Self::Ptr) -> &'a mut ImplicitTreap<T, U, A, Self> {
+        Rc::make_mut(ptr)
+    }
+    fn get_mut_path<'a>(
+        &'a mut self,
+        ptr: &'a mut Self::Ptr,
+        path: &[bool],
+    ) -> &'a mut ImplicitTreap<T, U, A, Self> {
+        let mut node = Rc::make_mut(ptr);
+        for &right in path {
+            let next = if right { &mut node.r } else { &mut node.l };
+            node = Rc::make_mut(next.as_mut().unwrap());
+        }
+        return node;
+    }
+    fn free(&mut self, ptr: Self::Ptr) -> Option<ImplicitTreap<T, U, A, Self>> {
+        Rc::try_unwrap(ptr).ok()
+    }
+    fn take(&mut self, ptr: Self::Ptr) -> ImplicitTreap<T, U, A, Self> {
+        match Rc::try_unwrap(ptr) {
+            Ok(node) => node,
+            Err(ptr) => (*ptr).clone(),
+        }
+    }
+
+    fn clone_tree(&self, curr: &Link<T, U, A, Self>) -> (Self, Link<T, U, A, Self>) {
+        return (RcStorage, curr.clone());
+    }
+}
+
+impl<T: Clone, U: Clone, A: Clone> PointerStorage<T, U, A> for RcStorage {
+    fn node_mut(ptr: &mut Self::Ptr) -> &mut ImplicitTreap<T, U, A, Self> {
+        Rc::make_mut(ptr)
+    }
+}
+
+/// this trait is for the storages in which the pointers own their nodes, so a node can be reached without the storage
+/// then different nodes can be borrowed mutably at the same time, which is needed for the mutable iterators
+pub trait PointerStorage<T, U, A>: Storage<T, U, A> {
+    fn node_mut(ptr: &mut Self::Ptr) -> &mut ImplicitTreap<T, U, A, Self>;
+}
+
+/// this trait is for the containers of the functions' values in the nodes, they are used like Vec which is the container of the pointer storages
+pub trait Values<U>: Default + DerefMut<Target = [U]> {
+    fn push(&mut self, value: U);
+    fn clear(&mut self);
+    /// removes the value at ind and moves the next values one index back
+    fn remove(&mut self, ind: usize) -> U;
+    /// returns the number of bytes allocated by the container outside of the node
+    fn heap_bytes(&self) -> usize;
+
+    fn clone_values(&self) -> Self
+    where
+        U: Clone,
+    {
+        let mut values = Self::default();
+        for value in self.iter() {
+            values.push(value.clone());
+        }
+        return values;
+    }
+}
+
+impl<U> Values<U> for Vec<U> {
+    fn push(&mut self, value: U) {
+        Vec::push(self, value);
+    }
+    fn clear(&mut self) {
+        Vec::clear(self);
+    }
+    fn remove(&mut self, ind: usize) -> U {
+        Vec::remove(self, ind)
+    }
+    fn heap_bytes(&self) -> usize {
+        self.capacity() * mem::size_of::<U>()
+    }
+
+    fn clone_values(&self) -> Self
+    where
+        U: Clone,
+    {
+        return self.clone();
+    }
+}
+
 /// this is the node structure of implicit treap
-pub struct ImplicitTreap<T, U> {
+pub struct ImplicitTreap<T, U, A, S: Storage<T, U, A>> {
     /// cnt is the size of the subtree
-    cnt: u64,
+    pub(super) cnt: u64,
     /// y_key or priority for the node which is a random integer
-    y_key: i64,
+    pub(super) y_key: i64,
     /// the value stored in the node
-    value: T,
+    pub(super) value: T,
     /// value_all stores the functions' values for the subtree at that node
-    value_all: Vec<U>,
+    pub(super) value_all: S::Values,
+    /// value_all_rev stores the values of the non-commutative functions for the reversed subtree, they are swapped with the ones in value_all when the subtree is reversed
+    pub(super) value_all_rev: S::Values,
+    /// tag is the lazy action that is already applied to the node but should be pushed to its children
+    pub(super) tag: Option<A>,
+    /// rev shows that the node's children are swapped but the reversal should be pushed to them
+    pub(super) rev: bool,
 
     /// l is link to the left subtree of the node
-    l: Link<T, U>,
+    pub(super) l: Link<T, U, A, S>,
     /// r is link to the right subtree of the node
-    r: Link<T, U>,
+    pub(super) r: Link<T, U, A, S>,
+}
+
+/// the nodes are cloned when shared node is changed in persistent treap, the children are not cloned but shared
+impl<T: Clone, U: Clone, A: Clone, S: Storage<T, U, A>> Clone for ImplicitTreap<T, U, A, S>
+where
+    S::Ptr: Clone,
+{
+    fn clone(&self) -> Self {
+        ImplicitTreap::<T, U, A, S> {
+            cnt: self.cnt,
+            y_key: self.y_key,
+            value: self.value.clone(),
+            value_all: self.value_all.clone_values(),
+            value_all_rev: self.value_all_rev.clone_values(),
+            tag: self.tag.clone(),
+            rev: self.rev,
+
+            l: self.l.clone(),
+            r: self.r.clone(),
+        }
+    }
+}
+
+/// returns the node of non-empty link
+fn node<'a, T, U, A, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    storage: &'a S,
+) -> &'a ImplicitTreap<T, U, A, S> {
+    storage.get(curr.as_ref().unwrap())
+}
+/// returns the node of non-empty link for changing it
+fn node_mut<'a, T, U, A, S: Storage<T, U, A>>(
+    curr: &'a mut Link<T, U, A, S>,
+    storage: &'a mut S,
+) -> &'a mut ImplicitTreap<T, U, A, S> {
+    storage.get_mut(curr.as_mut().unwrap())
 }
 
 /// this function returns the number of elements at the subtree of some node
-pub fn get_cnt<T, U>(curr: &Link<T, U>) -> u64 {
+pub fn get_cnt<T, U, A, S: Storage<T, U, A>>(curr: &Link<T, U, A, S>, storage: &S) -> u64 {
     if curr.is_none() {
         return 0;
     }
-    return curr.as_ref().unwrap().cnt;
+    return node(curr, storage).cnt;
 }
 /// this function returns the number of elements at the subtree of some node
-pub fn get_values<T, U>(curr: &Link<T, U>) -> &[U] {
+pub fn get_values<'a, T: 'a, U: 'a, A: 'a, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    storage: &'a S,
+) -> &'a [U] {
     if curr.is_none() {
         return &[];
     }
-    return &curr.as_ref().unwrap().value_all;
+    return &node(curr, storage).value_all;
 }
 
 use rand::Rng;
-/// function for constructing a treap from a value and value all for an element
-pub fn make_treap<T, U>(value: T, value_all: Vec<U>) -> Link<T, U> {
-    Some(Box::new(ImplicitTreap::<T, U> {
+/// function for constructing a treap from a value and value all for an element, the y_key is taken from rng so the same rng gives the same treap
+/// value_all_rev should contain the values of the non-commutative functions, for one element they are the same as in value_all
+pub fn make_treap<T, U, A, R: Rng, S: Storage<T, U, A>>(
+    value: T,
+    value_all: S::Values,
+    value_all_rev: S::Values,
+    rng: &mut R,
+    storage: &mut S,
+) -> Link<T, U, A, S> {
+    Some(storage.alloc(ImplicitTreap::<T, U, A, S> {
         cnt: 1,
-        y_key: rand::thread_rng().gen::<i64>(),
+        y_key: rng.gen::<i64>(),
         value: value,
         value_all: value_all,
+        value_all_rev: value_all_rev,
+        tag: None,
+        rev: false,
 
         l: None,
         r: None,
     }))
 }
 
-/// one of the most important function - it recovers correct values of cnt and value_all using the functions slice
-fn recover<T, U>(curr: &mut Link<T, U>, functions: &[Func<T, U>]) {
+/// makes a treap with one node for value, with the values of all functions for it
+pub fn make_leaf<T, U, A, F: Aggregate<T, Value = U>, R: Rng, S: Storage<T, U, A>>(
+    value: T,
+    functions: &[F],
+    rev_functions: &[usize],
+    rng: &mut R,
+    storage: &mut S,
+) -> Link<T, U, A, S> {
+    let mut value_all = S::Values::default();
+    for function in functions.iter() {
+        value_all.push(function.leaf(&value));
+    }
+    let mut value_all_rev = S::Values::default();
+    for &i in rev_functions.iter() {
+        value_all_rev.push(functions[i].leaf(&value));
+    }
+    return make_treap(value, value_all, value_all_rev, rng, storage);
+}
+
+/// builds treap with the elements in their order in O(N) time, instead of merging them one by one
+/// the nodes on the right spine of the built part are kept in stack, every new node takes the nodes with smaller y_key from the top of the stack as its left subtree
+/// the right children of the nodes in the stack are attached when they are popped, so every node is recovered only once when its subtree is ready
+pub fn build_treap<T, U, A, I, F, R, S>(
+    elements: I,
+    functions: &[F],
+    rev_functions: &[usize],
+    rng: &mut R,
+    storage: &mut S,
+) -> Link<T, U, A, S>
+where
+    I: IntoIterator<Item = T>,
+    F: Aggregate<T, Value = U>,
+    R: Rng,
+    S: Storage<T, U, A>,
+{
+    let mut stack = Vec::<Link<T, U, A, S>>::new();
+    for element in elements {
+        let mut curr = make_leaf(element, functions, rev_functions, rng, storage);
+        let y_key = node(&curr, storage).y_key;
+        let mut last = None;
+        while !stack.is_empty() && node(stack.last().unwrap(), storage).y_key < y_key {
+            let mut top = stack.pop().unwrap();
+            node_mut(&mut top, storage).r = last;
+            recover(&mut top, functions, rev_functions, storage);
+            last = top;
+        }
+        node_mut(&mut curr, storage).l = last;
+        stack.push(curr);
+    }
+
+    let mut last = None;
+    while let Some(mut top) = stack.pop() {
+        node_mut(&mut top, storage).r = last;
+        recover(&mut top, functions, rev_functions, storage);
+        last = top;
+    }
+    return last;
+}
+
+/// applies action to the whole subtree of curr - the value of the node and value_all are changed now and the action is saved in the tag for the children
+/// rev_functions are the indices of the non-commutative functions whose values are in value_all_rev
+pub fn apply_action<T, U, A: Action<T, U>, S: Storage<T, U, A>>(
+    curr: &mut Link<T, U, A, S>,
+    action: &A,
+    rev_functions: &[usize],
+    storage: &mut S,
+) {
+    if curr.is_none() {
+        return;
+    }
+    let node = node_mut(curr, storage);
+    action.apply_value(&mut node.value);
+    let cnt = node.cnt;
+    for (i, fvalue) in node.value_all.iter_mut().enumerate() {
+        action.apply_fvalue(i, fvalue, cnt);
+    }
+    for (k, fvalue) in node.value_all_rev.iter_mut().enumerate() {
+        action.apply_fvalue(rev_functions[k], fvalue, cnt);
+    }
+    match node.tag {
+        None => node.tag = Some(action.clone()),
+        Some(ref mut tag) => tag.compose(action),
+    }
+}
+
+/// reverses the whole subtree of curr - the children of the node are swapped now, and the values of the non-commutative functions are swapped with the reversed ones
+pub fn apply_rev<T, U, A, S: Storage<T, U, A>>(
+    curr: &mut Link<T, U, A, S>,
+    rev_functions: &[usize],
+    storage: &mut S,
+) {
     if curr.is_none() {
         return;
     }
-    let node = curr.as_mut().unwrap();
+    let node = node_mut(curr, storage);
+    mem::swap(&mut node.l, &mut node.r);
+    for (k, &i) in rev_functions.iter().enumerate() {
+        mem::swap(&mut node.value_all[i], &mut node.value_all_rev[k]);
+    }
+    node.rev = !node.rev;
+}
+
+/// pushes the tag and the reversal of the node to its children, it should be called before the children of some node are changed or visited
+pub fn push<T, U, A: Action<T, U>, S: Storage<T, U, A>>(
+    curr: &mut Link<T, U, A, S>,
+    rev_functions: &[usize],
+    storage: &mut S,
+) {
+    if curr.is_none() {
+        return;
+    }
+    let node = node_mut(curr, storage);
+    if node.tag.is_none() && !node.rev {
+        return;
+    }
+    let tag = node.tag.take();
+    let rev = mem::replace(&mut node.rev, false);
+    let mut l = node.l.take();
+    let mut r = node.r.take();
+    if let Some(tag) = tag {
+        apply_action(&mut l, &tag, rev_functions, storage);
+        apply_action(&mut r, &tag, rev_functions, storage);
+    }
+    if rev {
+        apply_rev(&mut l, rev_functions, storage);
+        apply_rev(&mut r, rev_functions, storage);
+    }
+    let node = node_mut(curr, storage);
+    node.l = l;
+    node.r = r;
+}
+
+/// one of the most important function - it recovers correct values of cnt, value_all and value_all_rev using the functions slice
+fn recover<T, U, A, F: Aggregate<T, Value = U>, S: Storage<T, U, A>>(
+    curr: &mut Link<T, U, A, S>,
+    functions: &[F],
+    rev_functions: &[usize],
+    storage: &mut S,
+) {
+    if curr.is_none() {
+        return;
+    }
+    // the old vectors are taken out of the node, so their memory is reused while the children are read
+    let node_ref = node_mut(curr, storage);
+    let mut value_all = mem::take(&mut node_ref.value_all);
+    let mut value_all_rev = mem::take(&mut node_ref.value_all_rev);
+
+    let node = node(curr, storage);
+    let l = node.l.as_ref().map(|ptr| storage.get(ptr));
+    let r = node.r.as_ref().map(|ptr| storage.get(ptr));
     let mut curr_ind = 1;
-    if node.l.is_some() {
-        curr_ind += node.l.as_ref().unwrap().cnt;
+    if let Some(l) = l {
+        curr_ind += l.cnt;
     }
-    if node.r.is_some() {
-        curr_ind += node.r.as_ref().unwrap().cnt;
+    if let Some(r) = r {
+        curr_ind += r.cnt;
     }
+
+    value_all.clear();
+    for (i, function) in functions.iter().enumerate() {
+        let l_data = l.map(|l| (&l.value_all[i], l.cnt));
+        let r_data = r.map(|r| (&r.value_all[i], r.cnt));
+        value_all.push(function.compute(l_data, &node.value, r_data));
+    }
+
+    // for the reversed order of the subtree the reversed right part is on the left and the reversed left part is on the right
+    value_all_rev.clear();
+    for (k, &i) in rev_functions.iter().enumerate() {
+        let l_data = r.map(|r| (&r.value_all_rev[k], r.cnt));
+        let r_data = l.map(|l| (&l.value_all_rev[k], l.cnt));
+        value_all_rev.push(functions[i].compute(l_data, &node.value, r_data));
+    }
+
+    let node = node_mut(curr, storage);
     node.cnt = curr_ind;
+    node.value_all = value_all;
+    node.value_all_rev = value_all_rev;
+}
 
-    let len = functions.len();
-    node.value_all.clear();
-    for i in 0..len {
-        let function = &functions[i];
+/// recovers the values of all nodes whose subtrees contain elements from the segment [beg; end), it is used after the elements in the segment are changed
+/// first these nodes are detached from their parents in pre-order, then they are recovered and attached back in reversed order, so the children are recovered before their parents
+pub fn recover_segment<T, U, A: Action<T, U>, F: Aggregate<T, Value = U>, S: Storage<T, U, A>>(
+    curr: &mut Link<T, U, A, S>,
+    beg: u64,
+    end: u64,
+    functions: &[F],
+    rev_functions: &[usize],
+    storage: &mut S,
+) {
+    if curr.is_none() || end <= beg {
+        return;
+    }
+    // every detached node has the index of its parent and the side where it is attached - true for right
+    let mut detached = Vec::<(Link<T, U, A, S>, usize, bool)>::new();
+    let mut stack = vec![(curr.take(), beg, end, 0, false)];
+    while let Some((mut sub, beg, end, parent, right)) = stack.pop() {
+        push(&mut sub, rev_functions, storage);
+        let curr_len = get_cnt(&node(&sub, storage).l, storage) + 1;
+        let ind = detached.len();
+        if beg < curr_len - 1 {
+            let child = node_mut(&mut sub, storage).l.take();
+            stack.push((child, beg, cmp::min(end, curr_len - 1), ind, false));
+        }
+        if curr_len < end {
+            let child = node_mut(&mut sub, storage).r.take();
+            let child_beg = cmp::max(beg, curr_len) - curr_len;
+            stack.push((child, child_beg, end - curr_len, ind, true));
+        }
+        detached.push((sub, parent, right));
+    }
 
-        let mut l_data = None;
-        if node.l.is_some() {
-            l_data = Some((
-                &node.l.as_ref().unwrap().value_all[i],
-                node.l.as_ref().unwrap().cnt,
-            ));
+    while let Some((mut sub, parent, right)) = detached.pop() {
+        recover(&mut sub, functions, rev_functions, storage);
+        if detached.is_empty() {
+            *curr = sub;
+        } else if right {
+            node_mut(&mut detached[parent].0, storage).r = sub;
+        } else {
+            node_mut(&mut detached[parent].0, storage).l = sub;
         }
-        let mut r_data = None;
-        if node.r.is_some() {
-            r_data = Some((
-                &node.r.as_ref().unwrap().value_all[i],
-                node.r.as_ref().unwrap().cnt,
-            ));
+    }
+}
+
+/// detaches all nodes of the treap in pre-order with their tags and reversals pushed, then every node is changed by change and attached back in reversed order
+/// in this way the children are changed before their parents and every node is visited once
+fn change_all_nodes<T, U, A, S, C>(
+    curr: &mut Link<T, U, A, S>,
+    rev_functions: &[usize],
+    storage: &mut S,
+    mut change: C,
+) where
+    A: Action<T, U>,
+    S: Storage<T, U, A>,
+    C: FnMut(&mut Link<T, U, A, S>, &mut S),
+{
+    if curr.is_none() {
+        return;
+    }
+    // every detached node has the index of its parent and the side where it is attached - true for right
+    let mut detached = Vec::<(Link<T, U, A, S>, usize, bool)>::new();
+    let mut stack = vec![(curr.take(), 0, false)];
+    while let Some((mut sub, parent, right)) = stack.pop() {
+        push(&mut sub, rev_functions, storage);
+        let ind = detached.len();
+        let node = node_mut(&mut sub, storage);
+        let l = node.l.take();
+        let r = node.r.take();
+        if l.is_some() {
+            stack.push((l, ind, false));
+        }
+        if r.is_some() {
+            stack.push((r, ind, true));
+        }
+        detached.push((sub, parent, right));
+    }
+
+    while let Some((mut sub, parent, right)) = detached.pop() {
+        change(&mut sub, storage);
+        if detached.is_empty() {
+            *curr = sub;
+        } else if right {
+            node_mut(&mut detached[parent].0, storage).r = sub;
+        } else {
+            node_mut(&mut detached[parent].0, storage).l = sub;
         }
-        node.value_all.push(function(l_data, &node.value, r_data));
     }
 }
+
+/// computes the values of function for all nodes in one pass and adds them at the end of value_all, for non-commutative function the values for the reversed subtrees are added at the end of value_all_rev
+/// ind is the index of the new function, the treap should maintain the functions before it and rev_functions are their non-commutative ones
+pub fn add_function_values<T, U, A, F, S>(
+    curr: &mut Link<T, U, A, S>,
+    function: &F,
+    ind: usize,
+    rev_functions: &[usize],
+    storage: &mut S,
+) where
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    S: Storage<T, U, A>,
+{
+    let rev_ind = if function.is_commutative() {
+        None
+    } else {
+        Some(rev_functions.len())
+    };
+    change_all_nodes(curr, rev_functions, storage, |sub, storage| {
+        // the children are attached back with their new values, so the value of the node is computed from them like in recover
+        let node = node(sub, storage);
+        let l = node.l.as_ref().map(|ptr| storage.get(ptr));
+        let r = node.r.as_ref().map(|ptr| storage.get(ptr));
+        let value = function.compute(
+            l.map(|l| (&l.value_all[ind], l.cnt)),
+            &node.value,
+            r.map(|r| (&r.value_all[ind], r.cnt)),
+        );
+        let rev_value = match rev_ind {
+            None => None,
+            Some(k) => Some(function.compute(
+                r.map(|r| (&r.value_all_rev[k], r.cnt)),
+                &node.value,
+                l.map(|l| (&l.value_all_rev[k], l.cnt)),
+            )),
+        };
+
+        let node = node_mut(sub, storage);
+        node.value_all.push(value);
+        if let Some(rev_value) = rev_value {
+            node.value_all_rev.push(rev_value);
+        }
+    });
+}
+
+/// drops the values of the function with index ind from all nodes in one pass, rev_functions are the non-commutative functions before the removal
+pub fn remove_function_values<T, U, A, S>(
+    curr: &mut Link<T, U, A, S>,
+    ind: usize,
+    rev_functions: &[usize],
+    storage: &mut S,
+) where
+    A: Action<T, U>,
+    S: Storage<T, U, A>,
+{
+    let rev_ind = rev_functions.iter().position(|&i| i == ind);
+    change_all_nodes(curr, rev_functions, storage, |sub, storage| {
+        let node = node_mut(sub, storage);
+        node.value_all.remove(ind);
+        if let Some(k) = rev_ind {
+            node.value_all_rev.remove(k);
+        }
+    });
+}
+
 use std::mem;
 /// another important function which splits the implicit treap in two treaps - left treap with elements before ind and right treap with elements with index equal or greater to ind
-pub fn split<T, U>(
-    curr: &mut Link<T, U>,
-    ind: u64,
-    mut l_part: &mut Link<T, U>,
-    mut r_part: &mut Link<T, U>,
-    functions: &[Func<T, U>],
+/// the nodes on the path are detached from their children - the nodes going to the left treap are saved in l_path and the others in r_path
+/// every node in l_path should get the next one as its right child and every node in r_path should get the next one as its left child, so both treaps are built from the bottom
+pub fn split<T, U, A: Action<T, U>, F: Aggregate<T, Value = U>, S: Storage<T, U, A>>(
+    curr: &mut Link<T, U, A, S>,
+    mut ind: u64,
+    l_part: &mut Link<T, U, A, S>,
+    r_part: &mut Link<T, U, A, S>,
+    functions: &[F],
+    rev_functions: &[usize],
+    storage: &mut S,
 ) {
-    match curr {
-        None => {
-            *l_part = None;
-            *r_part = None;
-        }
-        Some(ref node) => {
-            let mut curr_len = 1;
-            if node.l.is_some() {
-                curr_len += node.l.as_ref().unwrap().cnt;
-            }
-            if curr_len <= ind {
-                *l_part = mem::replace(&mut *curr, None);
-                let mut temp = None;
-                split(
-                    &mut l_part.as_mut().unwrap().r,
-                    ind - curr_len,
-                    &mut temp,
-                    &mut r_part,
-                    functions,
-                );
-                l_part.as_mut().unwrap().r = temp;
-                recover(&mut l_part, functions);
-            } else {
-                *r_part = mem::replace(&mut *curr, None);
-                let mut temp = None;
-                split(
-                    &mut r_part.as_mut().unwrap().l,
-                    ind,
-                    &mut l_part,
-                    &mut temp,
-                    functions,
-                );
-                r_part.as_mut().unwrap().l = temp;
-                recover(&mut r_part, functions);
-            }
+    let mut l_path = Vec::<Link<T, U, A, S>>::new();
+    let mut r_path = Vec::<Link<T, U, A, S>>::new();
+    let mut sub = curr.take();
+    while sub.is_some() {
+        push(&mut sub, rev_functions, storage);
+        let curr_len = get_cnt(&node(&sub, storage).l, storage) + 1;
+        if curr_len <= ind {
+            let next = node_mut(&mut sub, storage).r.take();
+            ind -= curr_len;
+            l_path.push(sub);
+            sub = next;
+        } else {
+            let next = node_mut(&mut sub, storage).l.take();
+            r_path.push(sub);
+            sub = next;
         }
     }
+
+    let mut l_sub = None;
+    while let Some(mut top) = l_path.pop() {
+        node_mut(&mut top, storage).r = l_sub;
+        recover(&mut top, functions, rev_functions, storage);
+        l_sub = top;
+    }
+    let mut r_sub = None;
+    while let Some(mut top) = r_path.pop() {
+        node_mut(&mut top, storage).l = r_sub;
+        recover(&mut top, functions, rev_functions, storage);
+        r_sub = top;
+    }
+    *l_part = l_sub;
+    *r_part = r_sub;
 }
 /// the last important function - it merges the treaps l_part and r_part into curr, maintaining the order and using the y_keys
-pub fn merge<T, U>(
-    mut curr: &mut Link<T, U>,
-    mut l_part: &mut Link<T, U>,
-    mut r_part: &mut Link<T, U>,
-    functions: &[Func<T, U>],
+/// the nodes with bigger y_key are detached and saved in path with the side of their child that is not merged yet - true for right
+pub fn merge<T, U, A: Action<T, U>, F: Aggregate<T, Value = U>, S: Storage<T, U, A>>(
+    curr: &mut Link<T, U, A, S>,
+    l_part: &mut Link<T, U, A, S>,
+    r_part: &mut Link<T, U, A, S>,
+    functions: &[F],
+    rev_functions: &[usize],
+    storage: &mut S,
 ) {
-    if l_part.is_none() || r_part.is_none() {
-        if l_part.is_some() {
-            *curr = mem::replace(&mut *l_part, None);
+    let mut path = Vec::<(Link<T, U, A, S>, bool)>::new();
+    let mut l_sub = l_part.take();
+    let mut r_sub = r_part.take();
+    while l_sub.is_some() && r_sub.is_some() {
+        if node(&l_sub, storage).y_key > node(&r_sub, storage).y_key {
+            push(&mut l_sub, rev_functions, storage);
+            let next = node_mut(&mut l_sub, storage).r.take();
+            path.push((l_sub, true));
+            l_sub = next;
         } else {
-            *curr = mem::replace(&mut *r_part, None);
+            push(&mut r_sub, rev_functions, storage);
+            let next = node_mut(&mut r_sub, storage).l.take();
+            path.push((r_sub, false));
+            r_sub = next;
         }
-        return;
     }
-    let mut temp = None;
-    if l_part.as_ref().unwrap().y_key > r_part.as_ref().unwrap().y_key {
-        *curr = mem::replace(&mut *l_part, None);
-        merge(
-            &mut temp,
-            &mut curr.as_mut().unwrap().r,
-            &mut r_part,
-            functions,
-        );
-        curr.as_mut().unwrap().r = temp;
-    } else {
-        *curr = mem::replace(&mut *r_part, None);
+
+    let mut sub = if l_sub.is_some() { l_sub } else { r_sub };
+    while let Some((mut top, right)) = path.pop() {
+        if right {
+            node_mut(&mut top, storage).r = sub;
+        } else {
+            node_mut(&mut top, storage).l = sub;
+        }
+        recover(&mut top, functions, rev_functions, storage);
+        sub = top;
+    }
+    *curr = sub;
+}
+
+/// helper function to Index trait for the extended array, it doesn't push the tags so it should be used only for treaps without actions
+/// flip shows if the subtree of curr should be reversed because of the pending reversals in the nodes above it
+pub fn find_index<'a, T: 'a, U: 'a, A: 'a, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    mut ind: u64,
+    mut flip: bool,
+    storage: &'a S,
+) -> &'a T {
+    let mut sub = curr;
+    loop {
+        let node = node(sub, storage);
+        let (l, r) = if flip {
+            (&node.r, &node.l)
+        } else {
+            (&node.l, &node.r)
+        };
+        let curr_len = get_cnt(l, storage) + 1;
+        flip ^= node.rev;
+        if curr_len == ind + 1 {
+            return &node.value;
+        } else if curr_len < ind + 1 {
+            ind -= curr_len;
+            sub = r;
+        } else {
+            sub = l;
+        }
+    }
+}
+/// the parts of a segment for the queries through &self - subtrees which should be visited and elements or whole subtrees which are in the segment, with their flips and pending actions
+/// the whole subtrees keep also their links, so they can be copied by clone_segment
+enum QueryPart<'a, T, U, A, S: Storage<T, U, A>> {
+    Visit(&'a Link<T, U, A, S>, u64, u64, bool, Option<A>),
+    Element(&'a ImplicitTreap<T, U, A, S>, Option<A>),
+    Whole(
+        &'a Link<T, U, A, S>,
+        &'a ImplicitTreap<T, U, A, S>,
+        bool,
+        Option<A>,
+    ),
+}
+
+/// the values of the functions for a segment with the number of its elements, None is for the empty segment
+type SegmentValues<U> = Option<(Vec<U>, u64)>;
+
+/// returns the actions pending for the children of node, its tag is older than the actions pending from above
+fn children_pending<T, U, A: Action<T, U>, S: Storage<T, U, A>>(
+    node: &ImplicitTreap<T, U, A, S>,
+    pending: &Option<A>,
+) -> Option<A> {
+    return match (&node.tag, pending) {
+        (None, _) => pending.clone(),
+        (Some(tag), None) => Some(tag.clone()),
+        (Some(tag), Some(newer)) => {
+            let mut tag = tag.clone();
+            tag.compose(newer);
+            Some(tag)
+        }
+    };
+}
+
+/// returns the values of the functions for an element or a whole subtree with the pending actions applied
+/// for the reversed subtrees the values of the non-commutative functions are in value_all_rev, rev_ind has their indices in it
+fn part_values<T, U: Clone, A: Action<T, U>, F, S: Storage<T, U, A>>(
+    part: &QueryPart<'_, T, U, A, S>,
+    functions: &[F],
+    rev_ind: &[Option<usize>],
+) -> (Vec<U>, u64)
+where
+    F: Aggregate<T, Value = U>,
+{
+    let mut values = Vec::<U>::new();
+    match part {
+        QueryPart::Element(node, pending) => {
+            for (i, function) in functions.iter().enumerate() {
+                let mut value = function.leaf(&node.value);
+                if let Some(action) = pending {
+                    action.apply_fvalue(i, &mut value, 1);
+                }
+                values.push(value);
+            }
+            return (values, 1);
+        }
+        QueryPart::Whole(_, node, flip, pending) => {
+            for (i, k) in rev_ind.iter().enumerate() {
+                let mut value = match k {
+                    Some(k) if *flip => node.value_all_rev[*k].clone(),
+                    _ => node.value_all[i].clone(),
+                };
+                if let Some(action) = pending {
+                    action.apply_fvalue(i, &mut value, node.cnt);
+                }
+                values.push(value);
+            }
+            return (values, node.cnt);
+        }
+        QueryPart::Visit(..) => panic!("Subtree which is not visited yet!"),
+    }
+}
+
+/// combines the values of two neighbouring segments, l is the left one
+fn combine_values<T, U: Clone, F: Aggregate<T, Value = U>>(
+    l: &SegmentValues<U>,
+    r: &SegmentValues<U>,
+    functions: &[F],
+) -> SegmentValues<U> {
+    return match (l, r) {
+        (None, _) => r.clone(),
+        (_, None) => l.clone(),
+        (Some((l_values, l_cnt)), Some((r_values, r_cnt))) => {
+            let mut values = Vec::<U>::new();
+            for (i, function) in functions.iter().enumerate() {
+                values.push(function.combine((&l_values[i], *l_cnt), (&r_values[i], *r_cnt)));
+            }
+            Some((values, l_cnt + r_cnt))
+        }
+    };
+}
+
+/// returns the indices in value_all_rev of the functions, None is for the commutative ones
+fn rev_indices(len: usize, rev_functions: &[usize]) -> Vec<Option<usize>> {
+    let mut rev_ind = vec![None; len];
+    for (k, &i) in rev_functions.iter().enumerate() {
+        rev_ind[i] = Some(k);
+    }
+    return rev_ind;
+}
+
+/// returns the elements and whole subtrees which make the segment [beg; end) in order, they are O(log(N))
+/// like find_index it doesn't push the tags, the actions that are pending from the nodes above are composed and saved with the parts
+fn segment_parts<'a, T, U, A: Action<T, U>, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    beg: u64,
+    end: u64,
+    storage: &'a S,
+) -> Vec<QueryPart<'a, T, U, A, S>> {
+    let mut parts = Vec::<QueryPart<'a, T, U, A, S>>::new();
+    let mut stack = vec![QueryPart::Visit(curr, beg, end, false, None)];
+    while let Some(part) = stack.pop() {
+        let (sub, beg, end, flip, pending) = match part {
+            QueryPart::Visit(sub, beg, end, flip, pending) => (sub, beg, end, flip, pending),
+            _ => {
+                parts.push(part);
+                continue;
+            }
+        };
+        if sub.is_none() || end <= beg {
+            continue;
+        }
+        let node = node(sub, storage);
+        if beg == 0 && end == node.cnt {
+            parts.push(QueryPart::Whole(sub, node, flip, pending));
+            continue;
+        }
+        let (l, r) = if flip {
+            (&node.r, &node.l)
+        } else {
+            (&node.l, &node.r)
+        };
+        let curr_len = get_cnt(l, storage) + 1;
+        let child_pending = children_pending(node, &pending);
+        let child_flip = flip ^ node.rev;
+        if curr_len < end {
+            let child_beg = cmp::max(beg, curr_len) - curr_len;
+            let child_pending = child_pending.clone();
+            stack.push(QueryPart::Visit(
+                r,
+                child_beg,
+                end - curr_len,
+                child_flip,
+                child_pending,
+            ));
+        }
+        if beg < curr_len && curr_len <= end {
+            stack.push(QueryPart::Element(node, pending));
+        }
+        stack.push(QueryPart::Visit(
+            l,
+            beg,
+            cmp::min(end, curr_len - 1),
+            child_flip,
+            child_pending,
+        ));
+    }
+    return parts;
+}
+
+/// returns the values of the functions for the elements with indices in [beg; end) without changing the treap, so it needs only &self
+/// the segment is made of O(log(N)) elements and whole subtrees, whose values are combined from left to right
+pub fn query_segment<'a, T: 'a, U: 'a + Clone, A: 'a + Action<T, U>, F, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    beg: u64,
+    end: u64,
+    functions: &[F],
+    rev_functions: &[usize],
+    storage: &'a S,
+) -> Vec<U>
+where
+    F: Aggregate<T, Value = U>,
+{
+    let rev_ind = rev_indices(functions.len(), rev_functions);
+    let mut result = None;
+    for part in segment_parts(curr, beg, end, storage).iter() {
+        let values = Some(part_values(part, functions, &rev_ind));
+        result = combine_values(&result, &values, functions);
+    }
+
+    return match result {
+        None => functions
+            .iter()
+            .map(|function| function.identity())
+            .collect(),
+        Some((values, _)) => values,
+    };
+}
+
+/// copies the elements with indices in [beg; end) to new storage without changing the treap, so like query_segment it needs only &self
+/// the whole subtrees of the segment are copied with clone_tree and get the actions and reversals pending from above, the single elements become new leaves with y_keys from rng, and the parts are merged from left to right
+/// with RcStorage the subtrees are shared, so it is O(log(N)^2) time, the other storages copy the M elements in O(M + log(N)^2) time
+pub fn clone_segment<T, U, A, F, R, S>(
+    curr: &Link<T, U, A, S>,
+    beg: u64,
+    end: u64,
+    functions: &[F],
+    rev_functions: &[usize],
+    rng: &mut R,
+    storage: &S,
+) -> (S, Link<T, U, A, S>)
+where
+    T: Clone,
+    U: Clone,
+    A: Action<T, U>,
+    F: Aggregate<T, Value = U>,
+    R: Rng,
+    S: Storage<T, U, A>,
+{
+    let mut new_storage = S::default();
+    let mut result = None;
+    for part in segment_parts(curr, beg, end, storage).into_iter() {
+        let mut sub = match part {
+            QueryPart::Element(node, pending) => {
+                let mut value = node.value.clone();
+                if let Some(action) = pending {
+                    action.apply_value(&mut value);
+                }
+                make_leaf(value, functions, rev_functions, rng, &mut new_storage)
+            }
+            QueryPart::Whole(sub, _, flip, pending) => {
+                let (mut part_storage, copy) = storage.clone_tree(sub);
+                let mut copy = new_storage.move_tree(copy, &mut part_storage);
+                if flip {
+                    apply_rev(&mut copy, rev_functions, &mut new_storage);
+                }
+                if let Some(action) = pending {
+                    apply_action(&mut copy, &action, rev_functions, &mut new_storage);
+                }
+                copy
+            }
+            QueryPart::Visit(..) => panic!("Subtree which is not visited yet!"),
+        };
+        let mut temp = None;
         merge(
             &mut temp,
-            &mut l_part,
-            &mut curr.as_mut().unwrap().l,
+            &mut result,
+            &mut sub,
             functions,
+            rev_functions,
+            &mut new_storage,
         );
-        curr.as_mut().unwrap().l = temp;
+        result = temp;
     }
-    recover(&mut curr, functions);
+    return (new_storage, result);
 }
 
-/// helper function to Index trait for the extended array
-pub fn find_index<T, U>(curr: &Link<T, U>, ind: u64) -> &T {
-    let mut curr_len = 1;
-    let node = curr.as_ref().unwrap();
-    if node.l.is_some() {
-        curr_len += node.l.as_ref().unwrap().cnt;
+/// returns the biggest end such that pred is true for the values of the functions for [beg; end), pred should be monotone - true for the short segments and false for the long ones
+/// the parts of [beg; N) are added while pred is true, then the first part for which it is false is descended to find the boundary, so pred is called O(log(N)) times
+pub fn max_right<'a, T: 'a, U: 'a + Clone, A: 'a + Action<T, U>, F, P, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    beg: u64,
+    mut pred: P,
+    functions: &[F],
+    rev_functions: &[usize],
+    storage: &'a S,
+) -> u64
+where
+    F: Aggregate<T, Value = U>,
+    P: FnMut(&[U]) -> bool,
+{
+    let rev_ind = rev_indices(functions.len(), rev_functions);
+    let mut result = None;
+    let mut end = beg;
+    let len = get_cnt(curr, storage);
+    let parts = segment_parts(curr, beg, len, storage);
+    for part in parts.into_iter() {
+        let values = part_values(&part, functions, &rev_ind);
+        let cnt = values.1;
+        let candidate = combine_values(&result, &Some(values), functions);
+        if pred(&candidate.as_ref().unwrap().0) {
+            result = candidate;
+            end += cnt;
+            continue;
+        }
+
+        let (mut node, mut flip, mut pending) = match part {
+            QueryPart::Whole(_, node, flip, pending) => (node, flip, pending),
+            _ => return end,
+        };
+        loop {
+            let (l, r) = if flip {
+                (&node.r, &node.l)
+            } else {
+                (&node.l, &node.r)
+            };
+            let child_pending = children_pending(node, &pending);
+            let child_flip = flip ^ node.rev;
+            if l.is_some() {
+                let l_node = self::node(l, storage);
+                let l_part = QueryPart::Whole(l, l_node, child_flip, child_pending.clone());
+                let values = Some(part_values(&l_part, functions, &rev_ind));
+                let candidate = combine_values(&result, &values, functions);
+                if !pred(&candidate.as_ref().unwrap().0) {
+                    node = l_node;
+                    flip = child_flip;
+                    pending = child_pending;
+                    continue;
+                }
+                result = candidate;
+                end += l_node.cnt;
+            }
+            let element = QueryPart::Element(node, pending);
+            let values = Some(part_values(&element, functions, &rev_ind));
+            let candidate = combine_values(&result, &values, functions);
+            if !pred(&candidate.as_ref().unwrap().0) {
+                return end;
+            }
+            result = candidate;
+            end += 1;
+            if r.is_none() {
+                return end;
+            }
+            node = self::node(r, storage);
+            flip = child_flip;
+            pending = child_pending;
+        }
     }
-    if curr_len == ind + 1 {
-        &node.value
-    } else if curr_len < ind + 1 {
-        find_index(&node.r, ind - curr_len)
-    } else {
-        find_index(&node.l, ind)
+    return end;
+}
+
+/// returns the smallest beg such that pred is true for the values of the functions for [beg; end), pred should be monotone - true for the short segments and false for the long ones
+/// it is like max_right, but the parts of [0; end) are added from right to left
+pub fn min_left<'a, T: 'a, U: 'a + Clone, A: 'a + Action<T, U>, F, P, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    end: u64,
+    mut pred: P,
+    functions: &[F],
+    rev_functions: &[usize],
+    storage: &'a S,
+) -> u64
+where
+    F: Aggregate<T, Value = U>,
+    P: FnMut(&[U]) -> bool,
+{
+    let rev_ind = rev_indices(functions.len(), rev_functions);
+    let mut result = None;
+    let mut beg = end;
+    let parts = segment_parts(curr, 0, end, storage);
+    for part in parts.into_iter().rev() {
+        let values = part_values(&part, functions, &rev_ind);
+        let cnt = values.1;
+        let candidate = combine_values(&Some(values), &result, functions);
+        if pred(&candidate.as_ref().unwrap().0) {
+            result = candidate;
+            beg -= cnt;
+            continue;
+        }
+
+        let (mut node, mut flip, mut pending) = match part {
+            QueryPart::Whole(_, node, flip, pending) => (node, flip, pending),
+            _ => return beg,
+        };
+        loop {
+            let (l, r) = if flip {
+                (&node.r, &node.l)
+            } else {
+                (&node.l, &node.r)
+            };
+            let child_pending = children_pending(node, &pending);
+            let child_flip = flip ^ node.rev;
+            if r.is_some() {
+                let r_node = self::node(r, storage);
+                let r_part = QueryPart::Whole(r, r_node, child_flip, child_pending.clone());
+                let values = Some(part_values(&r_part, functions, &rev_ind));
+                let candidate = combine_values(&values, &result, functions);
+                if !pred(&candidate.as_ref().unwrap().0) {
+                    node = r_node;
+                    flip = child_flip;
+                    pending = child_pending;
+                    continue;
+                }
+                result = candidate;
+                beg -= r_node.cnt;
+            }
+            let element = QueryPart::Element(node, pending);
+            let values = Some(part_values(&element, functions, &rev_ind));
+            let candidate = combine_values(&values, &result, functions);
+            if !pred(&candidate.as_ref().unwrap().0) {
+                return beg;
+            }
+            result = candidate;
+            beg -= 1;
+            if l.is_none() {
+                return beg;
+            }
+            node = self::node(l, storage);
+            flip = child_flip;
+            pending = child_pending;
+        }
     }
+    return beg;
 }
-/// helper function to IndexMut trait for the extended array
-pub fn find_mut_index<T, U>(curr: &mut Link<T, U>, ind: u64) -> &mut T {
-    let mut curr_len = 1;
-    let node = curr.as_mut().unwrap();
-    if node.l.is_some() {
-        curr_len += node.l.as_ref().unwrap().cnt;
+/// returns the number of elements for which pred is true, pred should be true for some prefix of the elements and false for the others, like in slice::partition_point
+/// like find_index it doesn't push the tags, so it should be used only for treaps without actions
+pub fn partition_point<T, U, A, P: FnMut(&T) -> bool, S: Storage<T, U, A>>(
+    curr: &Link<T, U, A, S>,
+    mut pred: P,
+    storage: &S,
+) -> u64 {
+    let mut result = 0;
+    let mut sub = curr;
+    let mut flip = false;
+    while sub.is_some() {
+        let node = node(sub, storage);
+        let (l, r) = if flip {
+            (&node.r, &node.l)
+        } else {
+            (&node.l, &node.r)
+        };
+        flip ^= node.rev;
+        if pred(&node.value) {
+            result += get_cnt(l, storage) + 1;
+            sub = r;
+        } else {
+            sub = l;
+        }
     }
-    if curr_len == ind + 1 {
-        &mut node.value
-    } else if curr_len < ind + 1 {
-        find_mut_index(&mut node.r, ind - curr_len)
-    } else {
-        find_mut_index(&mut node.l, ind)
+    return result;
+}
+/// pushes the tags on the path to the element with index ind and saves the path in path, false is for going to the left child and true for the right child
+/// the nodes on the path are detached from their parents while they are pushed and attached back after that
+pub fn push_path<T, U, A: Action<T, U>, S: Storage<T, U, A>>(
+    curr: &mut Link<T, U, A, S>,
+    mut ind: u64,
+    rev_functions: &[usize],
+    storage: &mut S,
+    path: &mut Vec<bool>,
+) {
+    let mut detached = Vec::<Link<T, U, A, S>>::new();
+    let mut sub = curr.take();
+    loop {
+        push(&mut sub, rev_functions, storage);
+        let curr_len = get_cnt(&node(&sub, storage).l, storage) + 1;
+        if curr_len == ind + 1 {
+            break;
+        }
+        let next;
+        if curr_len < ind + 1 {
+            ind -= curr_len;
+            next = node_mut(&mut sub, storage).r.take();
+            path.push(true);
+        } else {
+            next = node_mut(&mut sub, storage).l.take();
+            path.push(false);
+        }
+        detached.push(sub);
+        sub = next;
+    }
+
+    for &right in path.iter().rev() {
+        let mut parent = detached.pop().unwrap();
+        if right {
+            node_mut(&mut parent, storage).r = sub;
+        } else {
+            node_mut(&mut parent, storage).l = sub;
+        }
+        sub = parent;
     }
+    *curr = sub;
+}
+/// helper function to IndexMut trait for the extended array, the tags on the path to the element are pushed
+pub fn find_mut_index<'a, T, U: 'a, A: 'a + Action<T, U>, S: Storage<T, U, A>>(
+    curr: &'a mut Link<T, U, A, S>,
+    ind: u64,
+    rev_functions: &[usize],
+    storage: &'a mut S,
+) -> &'a mut T {
+    let mut path = Vec::<bool>::new();
+    push_path(curr, ind, rev_functions, storage, &mut path);
+    &mut storage.get_mut_path(curr.as_mut().unwrap(), &path).value
 }
 
-/// helper function to Drop trait for the extended array
-pub fn drop_treap<T, U>(curr: &mut Link<T, U>) {
-    if curr.is_none() {
-        return;
+/// returns the element at the end of path from curr, the tags on the path should be pushed with push_path
+pub fn find_path<'a, T, U: 'a, A: 'a, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    path: &[bool],
+    storage: &'a S,
+) -> &'a T {
+    let mut sub = node(curr, storage);
+    for &right in path {
+        let next = if right { &sub.r } else { &sub.l };
+        sub = node(next, storage);
+    }
+    return &sub.value;
+}
+
+/// returns the element at the end of path from curr for changing it, the tags on the path should be pushed with push_path
+pub fn find_mut_path<'a, T, U: 'a, A: 'a, S: Storage<T, U, A>>(
+    curr: &'a mut Link<T, U, A, S>,
+    path: &[bool],
+    storage: &'a mut S,
+) -> &'a mut T {
+    &mut storage.get_mut_path(curr.as_mut().unwrap(), path).value
+}
+
+/// recovers the values of the nodes on path from curr after the element at its end is changed, the tags on the path should be pushed
+/// the nodes are detached from their parents along the path, then they are recovered and attached back from the end of the path
+pub fn recover_path<T, U, A, F: Aggregate<T, Value = U>, S: Storage<T, U, A>>(
+    curr: &mut Link<T, U, A, S>,
+    path: &[bool],
+    functions: &[F],
+    rev_functions: &[usize],
+    storage: &mut S,
+) {
+    let mut detached = Vec::<Link<T, U, A, S>>::new();
+    let mut sub = curr.take();
+    for &right in path {
+        let next = if right {
+            node_mut(&mut sub, storage).r.take()
+        } else {
+            node_mut(&mut sub, storage).l.take()
+        };
+        detached.push(sub);
+        sub = next;
     }
-    let node = curr.as_mut().unwrap();
-    if node.l.is_some() {
-        drop_treap(&mut node.l);
+
+    recover(&mut sub, functions, rev_functions, storage);
+    for &right in path.iter().rev() {
+        let mut parent = detached.pop().unwrap();
+        if right {
+            node_mut(&mut parent, storage).r = sub;
+        } else {
+            node_mut(&mut parent, storage).l = sub;
+        }
+        recover(&mut parent, functions, rev_functions, storage);
+        sub = parent;
     }
-    if node.r.is_some() {
-        drop_treap(&mut node.r);
+    *curr = sub;
+}
+
+/// helper function to Drop trait for the extended array, the nodes shared with other trees are not dropped
+/// the nodes are dropped one by one with stack, because the recursive dropping of deep treap can overflow the stack
+pub fn drop_treap<T, U, A, S: Storage<T, U, A>>(curr: &mut Link<T, U, A, S>, storage: &mut S) {
+    let mut stack = Vec::<Link<T, U, A, S>>::new();
+    stack.push(curr.take());
+    while let Some(sub) = stack.pop() {
+        if sub.is_none() {
+            continue;
+        }
+        let node = storage.free(sub.unwrap());
+        if node.is_none() {
+            continue;
+        }
+        let mut node = node.unwrap();
+        stack.push(node.l.take());
+        stack.push(node.r.take());
     }
-    drop(node);
 }
 
-/// helper function to Clone trait for the extended array
-pub fn clone_treap<T, U>(curr: &Link<T, U>) -> Link<T, U>
+/// helper function to Clone trait for the extended array, it copies the nodes from storage to new_storage
+/// the nodes are copied in pre-order with the index of their parent and the side of it - true for right, and then they are attached to their parents in reversed order
+pub fn clone_treap<T, U, A, S: Storage<T, U, A>>(
+    curr: &Link<T, U, A, S>,
+    storage: &S,
+    new_storage: &mut S,
+) -> Link<T, U, A, S>
 where
     T: Clone,
     U: Clone,
+    A: Clone,
 {
     if curr.is_none() {
         return None;
     }
-    let node = curr.as_ref().unwrap();
-    let mut new_node = Box::new(ImplicitTreap::<T, U> {
-        cnt: node.cnt,
-        y_key: node.y_key,
-        value: node.value.clone(),
-        value_all: node.value_all.clone(),
+    let mut copied = Vec::<(ImplicitTreap<T, U, A, S>, usize, bool)>::new();
+    let mut stack = vec![(curr, 0, false)];
+    while let Some((sub, parent, right)) = stack.pop() {
+        let node = node(sub, storage);
+        let ind = copied.len();
+        copied.push((
+            ImplicitTreap::<T, U, A, S> {
+                cnt: node.cnt,
+                y_key: node.y_key,
+                value: node.value.clone(),
+                value_all: node.value_all.clone_values(),
+                value_all_rev: node.value_all_rev.clone_values(),
+                tag: node.tag.clone(),
+                rev: node.rev,
 
-        l: None,
-        r: None,
-    });
-    if node.l.is_some() {
-        new_node.l = clone_treap(&node.l);
-    }
-    if node.r.is_some() {
-        new_node.r = clone_treap(&node.r);
+                l: None,
+                r: None,
+            },
+            parent,
+            right,
+        ));
+        if node.l.is_some() {
+            stack.push((&node.l, ind, false));
+        }
+        if node.r.is_some() {
+            stack.push((&node.r, ind, true));
+        }
     }
-    drop(node);
 
-    Some(new_node)
+    loop {
+        let (new_node, parent, right) = copied.pop().unwrap();
+        let new_link = Some(new_storage.alloc(new_node));
+        if copied.is_empty() {
+            return new_link;
+        }
+        if right {
+            copied[parent].0.r = new_link;
+        } else {
+            copied[parent].0.l = new_link;
+        }
+    }
 }
 
-// helper function for to_vec method of extended array that collects all values in Vector v
-pub fn collect_elements<'a, T, U>(curr: &'a Link<T, U>, mut v: &mut Vec<&'a T>) {
-    if curr.is_none() {
-        return;
+// helper function for to_vec method of extended array that collects all values in Vector v, like find_index it doesn't push the tags and uses flip for the pending reversals
+// the stack keeps the nodes whose left subtrees are visited now, with their flips
+pub fn collect_elements<'a, T: 'a, U: 'a, A: 'a, S: Storage<T, U, A>>(
+    curr: &'a Link<T, U, A, S>,
+    v: &mut Vec<&'a T>,
+    flip: bool,
+    storage: &'a S,
+) {
+    let mut stack = Vec::<(&'a ImplicitTreap<T, U, A, S>, bool)>::new();
+    let mut sub = curr;
+    let mut sub_flip = flip;
+    loop {
+        while sub.is_some() {
+            let node = node(sub, storage);
+            stack.push((node, sub_flip));
+            sub = if sub_flip { &node.r } else { &node.l };
+            sub_flip ^= node.rev;
+        }
+        match stack.pop() {
+            None => return,
+            Some((node, node_flip)) => {
+                v.push(&node.value);
+                sub = if node_flip { &node.l } else { &node.r };
+                sub_flip = node_flip ^ node.rev;
+            }
+        }
     }
-    let node = curr.as_ref().unwrap();
-    collect_elements(&node.l, &mut v);
-    v.push(&node.value);
-    collect_elements(&node.r, &mut v);
 }
@@ -0,0 +1,87 @@
+/// Here is the read-only view of a segment of the exray, it is used like a slice of the elements in the segment
+use std::ops::Index;
+
+use crate::exray::aggregate::Aggregate;
+use crate::exray::exray::Exray;
+use crate::exray::iter::Iter;
+use crate::exray::treap::*;
+
+/// SegmentView borrows the exray and shows only the elements with indices in [beg; end), the indices of the view start from 0
+/// like Index it is only for exrays without actions, because the elements are read through &self
+pub struct SegmentView<'a, T, U, F, S: Storage<T, U, NoAction>> {
+    exray: &'a Exray<T, U, NoAction, F, S>,
+    beg: usize,
+    end: usize,
+}
+
+impl<'a, T, U, F, S: Storage<T, U, NoAction>> SegmentView<'a, T, U, F, S> {
+    pub(super) fn new(exray: &'a Exray<T, U, NoAction, F, S>, beg: usize, end: usize) -> Self {
+        SegmentView {
+            exray: exray,
+            beg: beg,
+            end: end,
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.beg
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.beg == self.end
+    }
+
+    /// returns the element with index ind in the view or None if it is out of the view
+    pub fn get(&self, ind: usize) -> Option<&'a T> {
+        if self.len() <= ind {
+            return None;
+        }
+        return self.exray.get(self.beg + ind);
+    }
+
+    /// returns iterator over the elements of the view
+    pub fn iter(&self) -> Iter<'a, T, U, S> {
+        // the range of the view is checked when it is made
+        return self.exray.iter_range(self.beg..self.end).unwrap();
+    }
+
+    pub fn to_vec(&self) -> Vec<&'a T> {
+        self.iter().collect()
+    }
+
+    /// returns the functions values for the elements of the view in O(log(N)) time
+    pub fn functions_values(&self) -> Vec<U>
+    where
+        F: Aggregate<T, Value = U>,
+        U: Clone,
+    {
+        return self
+            .exray
+            .range_functions_values(self.beg..self.end)
+            .unwrap();
+    }
+}
+
+impl<'a, T, U, F, S: Storage<T, U, NoAction>> Index<usize> for SegmentView<'a, T, U, F, S> {
+    type Output = T;
+
+    fn index(&self, ind: usize) -> &Self::Output {
+        match self.get(ind) {
+            None => panic!(
+                "index out of bounds: len is {} but the index is {}",
+                self.len(),
+                ind
+            ),
+            Some(element) => element,
+        }
+    }
+}
+
+impl<'a, T, U, F, S: Storage<T, U, NoAction>> IntoIterator for &SegmentView<'a, T, U, F, S> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, U, S>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -1,159 +1,55 @@
-/// Here are defined the functions according the function type in treap.rs that are maintained for the default case and the demo case of main
+/// Here are defined the aggregates according to the Aggregate trait in the exray module that are maintained for the default case and the demo case of main
 use std::cmp;
-use std::collections::HashMap;
+use std::rc::Rc;
 
 use crate::demo::Element;
+use extended_array::exray::{Avg, FnAggregate, Func, FunctionRegistry, Max, Sum};
 
-type Func<T, U> = fn(Option<(&U, u64)>, &T, Option<(&U, u64)>) -> U;
-
-/// this function stores the functions for maintaining sum, max and min in HashMap functions that are used in the default case of main - with integers
-pub fn fill_functions_i64(functions: &mut HashMap<String, Func<i64, i64>>) {
+/// this function registers the functions for maintaining sum, max and min in the registry functions that are used in the default case of main - with integers
+pub fn fill_functions_i64(functions: &mut FunctionRegistry<Func<i64, i64>>) {
     // here we don't need the second value of x and z because it is the number of elements on the left and on the right respectively
-    functions.insert(
-        "sum".to_string(),
-        |x: Option<(&i64, u64)>, y: &i64, z: Option<(&i64, u64)>| -> i64 {
-            match x {
-                None => match z {
-                    None => *y,
-                    Some(z) => *y + *z.0,
-                },
-                Some(x) => match z {
-                    None => *x.0 + *y,
-                    Some(z) => *x.0 + *y + *z.0,
-                },
-            }
-        },
-    );
-
-    functions.insert(
-        "max".to_string(),
-        |x: Option<(&i64, u64)>, y: &i64, z: Option<(&i64, u64)>| -> i64 {
-            match x {
-                None => match z {
-                    None => *y,
-                    Some(z) => cmp::max(*y, *z.0),
-                },
-                Some(x) => match z {
-                    None => cmp::max(*x.0, *y),
-                    Some(z) => cmp::max(cmp::max(*x.0, *y), *z.0),
-                },
-            }
-        },
-    );
-
-    functions.insert(
-        "min".to_string(),
-        |x: Option<(&i64, u64)>, y: &i64, z: Option<(&i64, u64)>| -> i64 {
-            match x {
-                None => match z {
-                    None => *y,
-                    Some(z) => cmp::min(*y, *z.0),
-                },
-                Some(x) => match z {
-                    None => cmp::min(*x.0, *y),
-                    Some(z) => cmp::min(cmp::min(*x.0, *y), *z.0),
-                },
-            }
-        },
-    );
+    functions.register(Rc::new(FnAggregate::new(
+        "sum",
+        |y: &i64| -> i64 { *y },
+        |x: (&i64, u64), z: (&i64, u64)| -> i64 { *x.0 + *z.0 },
+        || -> i64 { 0 },
+    )));
+
+    functions.register(Rc::new(FnAggregate::new(
+        "max",
+        |y: &i64| -> i64 { *y },
+        |x: (&i64, u64), z: (&i64, u64)| -> i64 { cmp::max(*x.0, *z.0) },
+        || -> i64 { i64::MIN },
+    )));
+
+    functions.register(Rc::new(FnAggregate::new(
+        "min",
+        |y: &i64| -> i64 { *y },
+        |x: (&i64, u64), z: (&i64, u64)| -> i64 { cmp::min(*x.0, *z.0) },
+        || -> i64 { i64::MAX },
+    )));
 }
 
-/// this function stores the functions for maintaining sum, max and min in HashMap functions that are used in the demo case of main - with Element structure containing coronavirus data and returns statistics for cases and deaths
-pub fn fill_functions_element(functions: &mut HashMap<String, Func<Element, (f64, f64)>>) {
-    // we use f64 because of average
-    functions.insert(
-        "avg".to_string(),
-        |x: Option<(&(f64, f64), u64)>, y: &Element, z: Option<(&(f64, f64), u64)>| -> (f64, f64) {
-            let yc = y.cases as f64;
-            let yd = y.deaths as f64;
-            match x {
-                None => match z {
-                    None => (yc, yd),
-                    Some(((zc, zd), cntz)) => { // (zc, zd) should be average values of right part
-                        let zcnt = cntz as f64;
-                        (
-                            (yc + (*zc) * zcnt) / (zcnt + 1.),
-                            (yd + (*zd) * zcnt) / (zcnt + 1.),
-                        )
-                    }
-                },
-                Some(((xc, xd), cntx)) => {
-                    let xcnt = cntx as f64;
-                    match z {
-                        None => (
-                            ((*xc) * xcnt + yc) / (xcnt + 1.),
-                            ((*xd) * xcnt + yd) / (xcnt + 1.),
-                        ),
-                        Some(((zc, zd), cntz)) => {
-                            let zcnt = cntz as f64;
-                            (
-                                ((*xc) * xcnt + yc + (*zc) * zcnt) / (xcnt + 1. + zcnt),
-                                ((*xd) * xcnt + yd + (*zd) * zcnt) / (xcnt + 1. + zcnt),
-                            )
-                        }
-                    }
-                }
-            }
-        },
-    );
-
-    functions.insert(
-        "max".to_string(),
-        |x: Option<(&(f64, f64), u64)>, y: &Element, z: Option<(&(f64, f64), u64)>| -> (f64, f64) {
-            let yc = y.cases;
-            let yd = y.deaths;
-            match x {
-                None => match z {
-                    None => (yc as f64, yd as f64),
-                    Some(((zc, zd), _)) => (
-                        cmp::max(yc, *zc as u64) as f64,
-                        cmp::max(yd, *zd as u64) as f64,
-                    ),
-                },
-                Some(((xc, xd), _)) => match z {
-                    None => (
-                        cmp::max(*xc as u64, yc) as f64,
-                        cmp::max(*xd as u64, yd) as f64,
-                    ),
-                    Some(((zc, zd), _)) => (
-                        cmp::max(*xc as u64, cmp::max(yc, *zc as u64)) as f64,
-                        cmp::max(*xd as u64, cmp::max(yd, *zd as u64)) as f64,
-                    ),
-                },
-            }
-        },
-    );
+/// the values of the aggregate for the demo - sum, max and average of the cases and then the same for the deaths
+/// every aggregate keeps its own type, so the sums and the maximums are exact integers and only the averages are f64
+pub type ElementValues = (u64, u64, f64, u64, u64, f64);
+
+/// this function returns the aggregate for the demo case of main - with Element structure containing coronavirus data, it is one typed tuple of aggregates
+pub fn element_aggregate() -> Func<Element, ElementValues> {
+    return Rc::new((
+        Sum::by("cases", |y: &Element| -> u64 { y.cases }),
+        Max::by("cases", |y: &Element| -> u64 { y.cases }),
+        Avg::by("cases", |y: &Element| -> f64 { y.cases as f64 }),
+        Sum::by("deaths", |y: &Element| -> u64 { y.deaths }),
+        Max::by("deaths", |y: &Element| -> u64 { y.deaths }),
+        Avg::by("deaths", |y: &Element| -> f64 { y.deaths as f64 }),
+    ));
+}
 
-    functions.insert(
-        "sum".to_string(),
-        |x: Option<(&(f64, f64), u64)>, y: &Element, z: Option<(&(f64, f64), u64)>| -> (f64, f64) {
-            let yc = y.cases as f64;
-            let yd = y.deaths as f64;
-            match x {
-                None => match z {
-                    None => (yc, yd),
-                    Some(((zc, zd), _)) => {
-                        (
-                            yc + (*zc),
-                            yd + (*zd),
-                        )
-                    }
-                },
-                Some(((xc, xd), _)) => {
-                    match z {
-                        None => (
-                            (*xc) + yc,
-                            (*xd) + yd,
-                        ),
-                        Some(((zc, zd), _)) => {
-                            (
-                                (*xc) + yc + (*zc),
-                                (*xd) + yd + (*zd),
-                            )
-                        }
-                    }
-                }
-            }
-        },
-    );
+/// this function registers the aggregate of the demo in the registry functions, it is registered under the id of the tuple
+/// the tuple has all of the statistics, so it is registered also under the short names avg, max and sum that the demo used for its functions
+pub fn fill_functions_element(functions: &mut FunctionRegistry<Func<Element, ElementValues>>) {
+    for alias in &["avg", "max", "sum"] {
+        functions.register_as(alias, element_aggregate());
+    }
 }
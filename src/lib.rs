@@ -0,0 +1,2 @@
+/// the exray module is the library with the extended array, the REPL and the demo in main are built on it
+pub mod exray;
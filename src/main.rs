@@ -1,13 +1,12 @@
 mod demo;
-mod exray;
 mod functions;
 use demo::*;
-use exray::*;
+use extended_array::exray::*;
 use functions::*;
 use io::Write;
 
 use std::io::{self, BufRead, BufReader, BufWriter, Lines, StdinLock};
-use std::{fmt::Display, fmt::Debug, fs::File, io::Error, str::FromStr};
+use std::{error, fmt, fmt::Display, fmt::Debug, fs::File, io::Error, str::FromStr};
 
 #[cfg(test)]
 mod tests {
@@ -63,7 +62,7 @@ mod tests {
 
         for i in 0..len {
             let expected_func = functions.get(&fn_names[i]).unwrap();
-            if exray_functions[i] as usize != *expected_func as usize {
+            if exray_functions[i].id() != expected_func.id() {
                 return false;
             }
         }
@@ -95,7 +94,7 @@ mod tests {
         let stdin = io::stdin();
         let mut line_it = stdin.lock().lines();
         let mut exrays = HashMap::<String, Exray<i64, i64>>::new();
-        let mut functions = HashMap::<String, Func<i64, i64>>::new();
+        let mut functions = FunctionRegistry::<Func<i64, i64>>::new();
         fill_functions_i64(&mut functions);
 
         write_to_file(
@@ -256,7 +255,7 @@ mod tests {
         let stdin = io::stdin();
         let mut line_it = stdin.lock().lines();
         let mut exrays = HashMap::<String, Exray<i64, i64>>::new();
-        let mut functions = HashMap::<String, Func<i64, i64>>::new();
+        let mut functions = FunctionRegistry::<Func<i64, i64>>::new();
         fill_functions_i64(&mut functions);
 
         write_to_file(
@@ -292,8 +291,7 @@ mod tests {
                     String::from("test"),
                     String::from("saved")
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
             None
@@ -330,8 +328,7 @@ mod tests {
                     String::from("test2"),
                     String::from("saved")
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
             None
@@ -364,14 +361,13 @@ mod tests {
                     String::from("no-exray"),
                     String::from("saved")
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
             Some(CommandError::SaveError(_))
         );
         assert_match!(
-            save(&[], &mut exrays, &functions).err(),
+            save(&[], &mut exrays).err(),
             Some(CommandError::SaveError(_))
         );
         assert_match!(
@@ -382,8 +378,7 @@ mod tests {
                     String::from("3"),
                     String::from("4")
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
             Some(CommandError::SaveError(_))
@@ -463,13 +458,13 @@ mod tests {
 
     #[test]
     fn test_change_element() {
-        let mut functions = HashMap::<String, Func<i64, i64>>::new();
+        let mut functions = FunctionRegistry::<Func<i64, i64>>::new();
         fill_functions_i64(&mut functions);
         let mut exrays = HashMap::<String, Exray<i64, i64>>::new();
         let mut numbers = vec![2, 9, -5, 10, 1024];
         exrays.insert(
             String::from("test"),
-            Exray::<i64, i64>::new(numbers.clone(), vec![*functions.get("sum").unwrap()]),
+            Exray::<i64, i64>::new(numbers.clone(), vec![functions.get("sum").unwrap().clone()]),
         );
         for i in 0..numbers.len() {
             assert_eq!(
@@ -506,8 +501,7 @@ mod tests {
             assert_eq!(
                 exray_fvalues(
                     &[String::from("1"), String::from("test")],
-                    &mut exrays,
-                    &functions
+                    &mut exrays
                 )
                 .unwrap(),
                 vec![(String::from("sum"), &sum)]
@@ -682,7 +676,7 @@ mod tests {
                 &mut exrays
             )
             .err(),
-            Some(CommandError::ExrayError(ExrayError::IndexError(_)))
+            Some(CommandError::ExrayError(ExrayError::IndexOutOfBounds { .. }))
         );
     }
 
@@ -702,7 +696,7 @@ mod tests {
                 &mut exrays
             )
             .err(),
-            Some(CommandError::ExrayError(ExrayError::IndexError(_)))
+            Some(CommandError::ExrayError(ExrayError::IndexOutOfBounds { .. }))
         );
         for ind in erase_indices {
             assert_eq!(
@@ -772,7 +766,7 @@ mod tests {
                 &mut exrays
             )
             .err(),
-            Some(CommandError::ExrayError(ExrayError::IndexError(_)))
+            Some(CommandError::ExrayError(ExrayError::IndexOutOfBounds { .. }))
         );
         assert_match!(
             erase_segment(
@@ -785,7 +779,7 @@ mod tests {
                 &mut exrays
             )
             .err(),
-            Some(CommandError::ExrayError(ExrayError::IndexError(_)))
+            Some(CommandError::ExrayError(ExrayError::InvalidRange { .. }))
         );
 
         assert_match!(
@@ -1039,7 +1033,7 @@ mod tests {
                 String::from("clone")
             )
             .err(),
-            Some(CommandError::ExrayError(ExrayError::IndexError(_)))
+            Some(CommandError::ExrayError(ExrayError::InvalidRange { .. }))
         );
 
         assert_match!(
@@ -1085,7 +1079,7 @@ mod tests {
                 String::from("extract")
             )
             .err(),
-            Some(CommandError::ExrayError(ExrayError::IndexError(_)))
+            Some(CommandError::ExrayError(ExrayError::InvalidRange { .. }))
         );
     }
 
@@ -1207,13 +1201,13 @@ mod tests {
                 &mut exrays
             )
             .err(),
-            Some(CommandError::ExrayError(ExrayError::IndexError(_)))
+            Some(CommandError::ExrayError(ExrayError::IndexOutOfBounds { .. }))
         );
     }
 
     #[test]
     fn test_segment_fvalues() {
-        let mut functions = HashMap::<String, Func<i64, i64>>::new();
+        let mut functions = FunctionRegistry::<Func<i64, i64>>::new();
         fill_functions_i64(&mut functions);
         let mut exrays = HashMap::<String, Exray<i64, i64>>::new();
         let numbers = vec![8, 20, 2, 15, 3, 18, 19, 1, 9, 8];
@@ -1222,9 +1216,9 @@ mod tests {
             Exray::<i64, i64>::new(
                 numbers.clone(),
                 vec![
-                    *functions.get("sum").unwrap(),
-                    *functions.get("max").unwrap(),
-                    *functions.get("min").unwrap(),
+                    functions.get("sum").unwrap().clone(),
+                    functions.get("max").unwrap().clone(),
+                    functions.get("min").unwrap().clone(),
                 ],
             ),
         );
@@ -1241,8 +1235,7 @@ mod tests {
                     2.to_string(),
                     4.to_string()
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .unwrap(),
             vec![
@@ -1259,8 +1252,7 @@ mod tests {
                     4.to_string(),
                     8.to_string()
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .unwrap(),
             vec![
@@ -1277,15 +1269,14 @@ mod tests {
                     4.to_string(),
                     8.to_string()
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .unwrap(),
             vec![]
         );
 
         assert_match!(
-            segment_fvalues(&[], &mut exrays, &functions).err(),
+            segment_fvalues(&[], &mut exrays).err(),
             Some(CommandError::SegmentFvaluesError(_))
         );
         assert_match!(
@@ -1297,8 +1288,7 @@ mod tests {
                     String::from("4"),
                     String::from("5")
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
             Some(CommandError::SegmentFvaluesError(_))
@@ -1311,8 +1301,7 @@ mod tests {
                     0.to_string(),
                     0.to_string()
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
             Some(CommandError::SegmentFvaluesError(_))
@@ -1325,8 +1314,7 @@ mod tests {
                     (-1).to_string(),
                     0.to_string()
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
             Some(CommandError::SegmentFvaluesError(_))
@@ -1339,8 +1327,7 @@ mod tests {
                     0.to_string(),
                     (-1).to_string()
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
             Some(CommandError::SegmentFvaluesError(_))
@@ -1353,11 +1340,10 @@ mod tests {
                     3.to_string(),
                     10.to_string()
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
-            Some(CommandError::ExrayError(ExrayError::IndexError(_)))
+            Some(CommandError::ExrayError(ExrayError::IndexOutOfBounds { .. }))
         );
         assert_match!(
             segment_fvalues(
@@ -1367,17 +1353,16 @@ mod tests {
                     5.to_string(),
                     3.to_string()
                 ],
-                &mut exrays,
-                &functions
+                &mut exrays
             )
             .err(),
-            Some(CommandError::ExrayError(ExrayError::IndexError(_)))
+            Some(CommandError::ExrayError(ExrayError::InvalidRange { .. }))
         );
     }
 
     #[test]
     fn test_exray_fvalues() {
-        let mut functions = HashMap::<String, Func<i64, i64>>::new();
+        let mut functions = FunctionRegistry::<Func<i64, i64>>::new();
         fill_functions_i64(&mut functions);
         let mut exrays = HashMap::<String, Exray<i64, i64>>::new();
         let numbers = vec![8, 20, 2, 15, 3, 18, 19, 1, 9, 8];
@@ -1386,9 +1371,9 @@ mod tests {
             Exray::<i64, i64>::new(
                 numbers.clone(),
                 vec![
-                    *functions.get("sum").unwrap(),
-                    *functions.get("max").unwrap(),
-                    *functions.get("min").unwrap(),
+                    functions.get("sum").unwrap().clone(),
+                    functions.get("max").unwrap().clone(),
+                    functions.get("min").unwrap().clone(),
                 ],
             ),
         );
@@ -1400,8 +1385,7 @@ mod tests {
         assert_eq!(
             exray_fvalues(
                 &[String::from("1"), String::from("test")],
-                &exrays,
-                &functions
+                &exrays
             )
             .unwrap(),
             vec![
@@ -1413,22 +1397,20 @@ mod tests {
         assert_eq!(
             exray_fvalues(
                 &[String::from("1"), String::from("test2")],
-                &exrays,
-                &functions
+                &exrays
             )
             .unwrap(),
             vec![]
         );
 
         assert_match!(
-            exray_fvalues(&[], &exrays, &functions).err(),
+            exray_fvalues(&[], &exrays).err(),
             Some(CommandError::ExrayFvaluesError(_))
         );
         assert_match!(
             exray_fvalues(
                 &[String::from("1"), String::from("2"), String::from("3")],
-                &exrays,
-                &functions
+                &exrays
             )
             .err(),
             Some(CommandError::ExrayFvaluesError(_))
@@ -1436,13 +1418,216 @@ mod tests {
         assert_match!(
             exray_fvalues(
                 &[String::from("1"), String::from("no-exray")],
-                &exrays,
-                &functions
+                &exrays
             )
             .err(),
             Some(CommandError::ExrayFvaluesError(_))
         );
     }
+
+    #[test]
+    fn test_add_remove_function() {
+        let mut functions = FunctionRegistry::<Func<i64, i64>>::new();
+        fill_functions_i64(&mut functions);
+        let mut exrays = HashMap::<String, Exray<i64, i64>>::new();
+        let numbers = vec![8, 20, 2, 15, 3];
+        exrays.insert(
+            String::from("test"),
+            Exray::<i64, i64>::new(numbers.clone(), vec![functions.get("sum").unwrap().clone()]),
+        );
+
+        assert_match!(
+            add_function(
+                &[String::from("1"), String::from("test"), String::from("min")],
+                &mut exrays,
+                &functions
+            )
+            .err(),
+            None
+        );
+        assert_match!(
+            add_function(
+                &[String::from("1"), String::from("test"), String::from("max")],
+                &mut exrays,
+                &functions
+            )
+            .err(),
+            None
+        );
+        assert_eq!(
+            exray_fvalues(&[String::from("1"), String::from("test")], &exrays).unwrap(),
+            vec![
+                (String::from("sum"), &48),
+                (String::from("min"), &2),
+                (String::from("max"), &20)
+            ]
+        );
+        assert_match!(
+            add_function(
+                &[String::from("1"), String::from("test"), String::from("min")],
+                &mut exrays,
+                &functions
+            )
+            .err(),
+            Some(CommandError::AddFunctionError(_))
+        );
+        assert_match!(
+            add_function(
+                &[String::from("1"), String::from("test"), String::from("avg")],
+                &mut exrays,
+                &functions
+            )
+            .err(),
+            Some(CommandError::AddFunctionError(_))
+        );
+        assert_match!(
+            add_function(&[String::from("1")], &mut exrays, &functions).err(),
+            Some(CommandError::AddFunctionError(_))
+        );
+
+        assert_eq!(
+            remove_function(
+                &[String::from("1"), String::from("test"), String::from("sum")],
+                &mut exrays
+            )
+            .unwrap(),
+            String::from("sum")
+        );
+        assert_eq!(
+            remove_function(
+                &[String::from("1"), String::from("test"), String::from("1")],
+                &mut exrays
+            )
+            .unwrap(),
+            String::from("max")
+        );
+        assert_eq!(
+            exray_fvalues(&[String::from("1"), String::from("test")], &exrays).unwrap(),
+            vec![(String::from("min"), &2)]
+        );
+        assert_match!(
+            remove_function(
+                &[String::from("1"), String::from("test"), String::from("max")],
+                &mut exrays
+            )
+            .err(),
+            Some(CommandError::RemoveFunctionError(_))
+        );
+        assert_match!(
+            remove_function(
+                &[String::from("1"), String::from("test"), String::from("1")],
+                &mut exrays
+            )
+            .err(),
+            Some(CommandError::ExrayError(
+                ExrayError::IndexOutOfBounds { .. }
+            ))
+        );
+
+        // the function with number for name is removed by its name, not by the index
+        functions.register(std::rc::Rc::new(FnAggregate::new(
+            "1",
+            |y: &i64| -> i64 { *y },
+            |x: (&i64, u64), _z: (&i64, u64)| -> i64 { *x.0 },
+            || -> i64 { 0 },
+        )));
+        assert_match!(
+            add_function(
+                &[String::from("1"), String::from("test"), String::from("1")],
+                &mut exrays,
+                &functions
+            )
+            .err(),
+            None
+        );
+        assert_match!(
+            add_function(
+                &[String::from("1"), String::from("test"), String::from("sum")],
+                &mut exrays,
+                &functions
+            )
+            .err(),
+            None
+        );
+        assert_eq!(
+            remove_function(
+                &[String::from("1"), String::from("test"), String::from("1")],
+                &mut exrays
+            )
+            .unwrap(),
+            String::from("1")
+        );
+        assert_eq!(
+            exray_fvalues(&[String::from("1"), String::from("test")], &exrays).unwrap(),
+            vec![(String::from("min"), &2), (String::from("sum"), &48)]
+        );
+        assert_match!(
+            remove_function(
+                &[
+                    String::from("1"),
+                    String::from("no-exray"),
+                    String::from("0")
+                ],
+                &mut exrays
+            )
+            .err(),
+            Some(CommandError::RemoveFunctionError(_))
+        );
+    }
+
+    #[test]
+    fn test_element_aggregate() {
+        let data = vec![
+            "austria;2020-01;5;1".parse::<Element>().unwrap(),
+            "austria;2020-02;3;2".parse::<Element>().unwrap(),
+        ];
+        let mut functions = FunctionRegistry::<Func<Element, ElementValues>>::new();
+        fill_functions_element(&mut functions);
+        let id = "(sum(cases),max(cases),avg(cases),sum(deaths),max(deaths),avg(deaths))";
+        assert_eq!(functions.ids(), vec![id, "avg", "max", "sum"]);
+        assert_eq!(functions.get("max").unwrap().id(), id);
+
+        let mut exray =
+            Exray::<Element, ElementValues>::with_aggregate(data, element_aggregate());
+        assert_eq!(exray.aggregate_value().unwrap(), (8, 5, 4., 3, 2, 1.5));
+        exray.push_back("belgium;2020-01;10;0".parse::<Element>().unwrap());
+        assert_eq!(
+            exray.range_aggregate_value(1..).unwrap(),
+            (13, 10, 6.5, 2, 2, 1.)
+        );
+        assert_eq!(
+            find_country_segment(String::from("Austria"), &exray),
+            Some((0, 1))
+        );
+
+        // the short names of the demo find the tuple, and it is added once
+        let stdin = io::stdin();
+        let mut line_it = stdin.lock().lines();
+        let mut exrays = HashMap::<String, Exray<Element, ElementValues>>::new();
+        write_to_file(
+            "test_create_element",
+            "austria;2020-01;5;1 austria;2020-02;3;2
+corona
+avg max sum",
+        );
+        assert_match!(
+            create(
+                &[String::from("create"), String::from("test_create_element")],
+                &mut line_it,
+                &mut exrays,
+                &functions
+            )
+            .err(),
+            None
+        );
+        let created = exrays.get("corona").unwrap();
+        assert_eq!(created.function_ids(), [id]);
+        assert_eq!(created.aggregate_value().unwrap(), (8, 5, 4., 3, 2, 1.5));
+        match remove_file("test_create_element") {
+            Err(_) => panic!("Cannot remove file test_create_element"),
+            _ => {}
+        }
+    }
 }
 
 #[derive(Debug)]
@@ -1466,6 +1651,44 @@ pub enum CommandError {
     SegmentFvaluesError(String),
     ExrayFvaluesError(String),
     ExrayLenError(String),
+    AddFunctionError(String),
+    RemoveFunctionError(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::IOError(e) => write!(f, "input error: {}", e),
+            CommandError::FileError(e) => write!(f, "file error: {}", e),
+            CommandError::ExrayError(e) => write!(f, "exray error: {}", e),
+            CommandError::InputEnd(message)
+            | CommandError::CreateError(message)
+            | CommandError::SaveError(message)
+            | CommandError::PrintError(message)
+            | CommandError::GetElementError(message)
+            | CommandError::ChangeElementError(message)
+            | CommandError::InsertElementError(message)
+            | CommandError::EraseElementError(message)
+            | CommandError::EraseSegmentError(message)
+            | CommandError::ExtractCloneSegmentError(message)
+            | CommandError::InsertExrayError(message)
+            | CommandError::SegmentFvaluesError(message)
+            | CommandError::ExrayFvaluesError(message)
+            | CommandError::ExrayLenError(message)
+            | CommandError::AddFunctionError(message)
+            | CommandError::RemoveFunctionError(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl error::Error for CommandError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match self {
+            CommandError::IOError(e) | CommandError::FileError(e) => Some(e),
+            CommandError::ExrayError(e) => Some(e),
+            _ => None,
+        }
+    }
 }
 
 /// this function has one parameter which is the result after reading line from stdin or file
@@ -1499,7 +1722,7 @@ fn try_line(result: Option<Result<String, Error>>) -> Result<Vec<String>, Comman
 }
 
 use std::collections::{HashMap, HashSet};
-type FuncMap<T, U> = HashMap<String, Func<T, U>>; /// HashMap storing names and functions associated with them
+type FuncMap<T, U> = FunctionRegistry<Func<T, U>>; /// registry storing the functions under their names
 type ExrayMap<T, U> = HashMap<String, Exray<T, U>>; /// HashMap storing names and associated exrays
 
 /// function creating exray reading data from stdin or file, depending on arguments
@@ -1603,20 +1826,23 @@ where
         Err(e) => return Err(e),
         Ok(words) => {
             for word in words {
-                if names.contains(&word) {
+                // names keeps the ids, so a function registered under several aliases is added once
+                let id = match functions.get(&word) {
+                    None => continue,
+                    Some(function) => function.id().into_owned(),
+                };
+                if names.contains(&id) {
                     continue;
                 }
-                if functions.contains_key(&word) {
-                    curr_names.push(word.clone());
-                    names.insert(word);
-                }
+                curr_names.push(word);
+                names.insert(id);
             }
         }
     }
 
     let mut curr_functions = Vec::<Func<T, U>>::new();
     for fn_name in curr_names {
-        curr_functions.push(*functions.get(&fn_name).unwrap());
+        curr_functions.push(functions.get(&fn_name).unwrap().clone());
     }
 
     exrays.insert(name.to_string(), Exray::<T, U>::new(nums, curr_functions));
@@ -1640,11 +1866,7 @@ fn check_name<T, U>(
 }
 
 /// saves exray into file in the same format as create expects
-fn save<T, U>(
-    words: &[String],
-    exrays: &ExrayMap<T, U>,
-    functions: &FuncMap<T, U>,
-) -> Result<(), CommandError>
+fn save<T, U>(words: &[String], exrays: &ExrayMap<T, U>) -> Result<(), CommandError>
 where
     T: Display,
 {
@@ -1675,15 +1897,10 @@ where
         Err(e) => return Err(CommandError::IOError(e)),
         _ => {}
     }
-    for exray_func in exray.functions() {
-        for (fn_name, func) in functions {
-            if *exray_func as usize == *func as usize {
-                match write!(writer, "{} ", fn_name) {
-                    Err(e) => return Err(CommandError::IOError(e)),
-                    _ => {}
-                }
-                break;
-            }
+    for fn_name in exray.function_ids() {
+        match write!(writer, "{} ", fn_name) {
+            Err(e) => return Err(CommandError::IOError(e)),
+            _ => {}
         }
     }
     match writeln!(writer) {
@@ -1695,11 +1912,7 @@ where
 }
 
 /// prints exray with name stored in argument words[1]
-fn print_exray<T, U>(
-    words: &[String],
-    exrays: &ExrayMap<T, U>,
-    functions: &FuncMap<T, U>,
-) -> Result<(), CommandError>
+fn print_exray<T, U>(words: &[String], exrays: &ExrayMap<T, U>) -> Result<(), CommandError>
 where
     T: Display,
 {
@@ -1718,13 +1931,8 @@ where
         print!("{} ", num);
     }
     print!("\n{}\n", words[1]);
-    for exray_func in exray.functions() {
-        for (fn_name, func) in functions {
-            if *exray_func as usize == *func as usize {
-                print!("{} ", fn_name);
-                break;
-            }
-        }
+    for fn_name in exray.function_ids() {
+        print!("{} ", fn_name);
     }
     println!();
 
@@ -1801,8 +2009,7 @@ where
         Ok(num) => num,
     };
 
-    exray[index] = new_value;
-    match exray.recover_fvalues(index) {
+    match exray.update(index, |value| *value = new_value) {
         Err(e) => return Err(CommandError::ExrayError(e)),
         Ok(_) => return Ok(()),
     }
@@ -2014,7 +2221,6 @@ fn insert_exray<T, U>(
 fn segment_fvalues<T, U>(
     words: &[String],
     exrays: &mut ExrayMap<T, U>,
-    functions: &FuncMap<T, U>,
 ) -> Result<Vec<(String, U)>, CommandError>
 where
     U: Clone,
@@ -2048,15 +2254,9 @@ where
         Err(e) => return Err(CommandError::ExrayError(e)),
         Ok(values) => {
             let mut fvalues = Vec::<(String, U)>::new();
-            let len = values.len();
-            let exray_funcs = exray.functions();
-            for i in 0..len {
-                for (fn_name, func) in functions {
-                    if exray_funcs[i] as usize == *func as usize {
-                        fvalues.push((fn_name.clone(), values[i].clone()));
-                        break;
-                    }
-                }
+            let fn_names = exray.function_ids();
+            for (fn_name, value) in fn_names.iter().zip(values) {
+                fvalues.push((fn_name.clone(), value));
             }
             return Ok(fvalues);
         }
@@ -2067,7 +2267,6 @@ where
 fn exray_fvalues<'a, T, U>(
     words: &[String],
     exrays: &'a ExrayMap<T, U>,
-    functions: &FuncMap<T, U>,
 ) -> Result<Vec<(String, &'a U)>, CommandError> {
     match check_name(words, 2, exrays) {
         Err(None) => {
@@ -2082,15 +2281,9 @@ fn exray_fvalues<'a, T, U>(
     let exray = exrays.get(&words[1]).unwrap();
     let values = exray.functions_values();
     let mut fvalues = Vec::<(String, &U)>::new();
-    let len = values.len();
-    let exray_funcs = exray.functions();
-    for i in 0..len {
-        for (fn_name, func) in functions {
-            if exray_funcs[i] as usize == *func as usize {
-                fvalues.push((fn_name.clone(), &values[i]));
-                break;
-            }
-        }
+    let fn_names = exray.function_ids();
+    for (fn_name, value) in fn_names.iter().zip(values) {
+        fvalues.push((fn_name.clone(), value));
     }
     return Ok(fvalues);
 }
@@ -2114,16 +2307,86 @@ fn exray_len<T, U>(
     return Ok((words[1].clone(), exray.len()));
 }
 
+/// starts maintaining the function with name words[2] in the exray with name words[1], the function is taken from the registry functions
+fn add_function<T, U>(
+    words: &[String],
+    exrays: &mut ExrayMap<T, U>,
+    functions: &FuncMap<T, U>,
+) -> Result<(), CommandError> {
+    match check_name(words, 3, exrays) {
+        Err(None) => {
+            return Err(CommandError::AddFunctionError(String::from(
+                "Two arguments expected - name of exray and name of function",
+            )));
+        }
+        Err(Some(e)) => return Err(CommandError::AddFunctionError(e)),
+        _ => {}
+    }
+    let function = match functions.get(&words[2]) {
+        None => {
+            return Err(CommandError::AddFunctionError(String::from(
+                "No function with that name",
+            )))
+        }
+        Some(function) => function.clone(),
+    };
+
+    let exray = exrays.get_mut(&words[1]).unwrap();
+    if exray.function_position(&words[2]).is_some() {
+        return Err(CommandError::AddFunctionError(String::from(
+            "The exray already maintains this function",
+        )));
+    }
+    exray.add_function(function);
+    return Ok(());
+}
+
+/// stops maintaining a function in the exray with name words[1], the function is given by its name or by its index in the functions of the exray
+/// if words[2] is both the name of a maintained function and an index, the function with that name is removed
+/// it returns the name of the removed function
+fn remove_function<T, U>(
+    words: &[String],
+    exrays: &mut ExrayMap<T, U>,
+) -> Result<String, CommandError> {
+    match check_name(words, 3, exrays) {
+        Err(None) => {
+            return Err(CommandError::RemoveFunctionError(String::from(
+                "Two arguments expected - name of exray and name or index of function",
+            )));
+        }
+        Err(Some(e)) => return Err(CommandError::RemoveFunctionError(e)),
+        _ => {}
+    }
+
+    let exray = exrays.get_mut(&words[1]).unwrap();
+    // the name is checked first, so functions with numbers for names can be removed by name too
+    let index = match exray.function_position(&words[2]) {
+        Some(index) => index,
+        None => match words[2].parse::<usize>() {
+            Ok(num) => num,
+            Err(_) => {
+                return Err(CommandError::RemoveFunctionError(String::from(
+                    "The exray doesn't maintain function with that name",
+                )))
+            }
+        },
+    };
+    match exray.remove_function(index) {
+        Err(e) => return Err(CommandError::ExrayError(e)),
+        Ok(function) => return Ok(function.id().into_owned()),
+    }
+}
+
 fn menu<T, U> (command_name: &String, words: Vec<String>, mut line_it: &mut Lines<StdinLock>, mut exrays: &mut ExrayMap<T, U>, functions: &FuncMap<T, U>)
     where T: FromStr + Display + Clone, <T as FromStr>::Err: Debug, U: Clone + Debug {
     if command_name == "create" {
         match create(&words, &mut line_it, &mut exrays, &functions) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok(name) => println!("Exray with name - {}, successfully added!", name),
         }
     } else if command_name == "save" {
-        match save(&words, &exrays, &functions) {
-            Err(e) => println!("{:?}", e),
+        match save(&words, &exrays) {
+            Err(e) => println!("{}", e),
             _ => println!("Exray successfully saved in file!"),
         }
     } else if command_name == "exray_names" {
@@ -2137,38 +2400,38 @@ fn menu<T, U> (command_name: &String, words: Vec<String>, mut line_it: &mut Line
         }
         println!();
     } else if command_name == "print" {
-        match print_exray(&words, &exrays, &functions) {
-            Err(e) => println!("{:?}", e),
+        match print_exray(&words, &exrays) {
+            Err(e) => println!("{}", e),
             _ => {}
         }
     } else if command_name == "get_element" {
         match get_element(&words, &exrays) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok(element) => println!("Element is - {}", element),
         }
     } else if command_name == "change_element" {
         match change_element(&words, &mut exrays) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok(_) => println!("Element changed successfully!"),
         }
     } else if command_name == "insert_element" {
         match insert_element(&words, &mut exrays) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok(_) => println!("Element inserted successfully!"),
         }
     } else if command_name == "erase_element" {
         match erase_element(&words, &mut exrays) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok(_) => println!("Element erased successfully!"),
         }
     } else if command_name == "erase_segment" {
         match erase_segment(&words, &mut exrays) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok(_) => println!("Segment erased successfully!"),
         }
     } else if command_name == "extract_segment" {
         match extract_or_clone_segment(&words, &mut exrays, String::from("extract")) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok(name) => println!(
                 "Segment extracted successfully as exray with name - {}",
                 name
@@ -2176,7 +2439,7 @@ fn menu<T, U> (command_name: &String, words: Vec<String>, mut line_it: &mut Line
         }
     } else if command_name == "insert_exray" {
         match insert_exray(&words, &mut exrays) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok(name) => println!(
                 "Exray with name {}, inserted successfully and removed from exrays!",
                 name
@@ -2184,30 +2447,40 @@ fn menu<T, U> (command_name: &String, words: Vec<String>, mut line_it: &mut Line
         }
     } else if command_name == "clone_segment" {
         match extract_or_clone_segment(&words, &mut exrays, String::from("clone")) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok(name) => {
                 println!("Segment cloned successfully as exray with name - {}", name)
             }
         }
     } else if command_name == "segment_fvalues" {
-        match segment_fvalues(&words, &mut exrays, &functions) {
-            Err(e) => println!("{:?}", e),
+        match segment_fvalues(&words, &mut exrays) {
+            Err(e) => println!("{}", e),
             Ok(fvalues) => println!("Segment function values - {:?}", fvalues),
         }
     } else if command_name == "exray_fvalues" {
-        match exray_fvalues(&words, &exrays, &functions) {
-            Err(e) => println!("{:?}", e),
+        match exray_fvalues(&words, &exrays) {
+            Err(e) => println!("{}", e),
             Ok(fvalues) => {
                 println!("Exray function values for all numbers - {:?}", fvalues)
             }
         }
+    } else if command_name == "add_function" {
+        match add_function(&words, &mut exrays, &functions) {
+            Err(e) => println!("{}", e),
+            Ok(_) => println!("Function added successfully!"),
+        }
+    } else if command_name == "remove_function" {
+        match remove_function(&words, &mut exrays) {
+            Err(e) => println!("{}", e),
+            Ok(name) => println!("Function {} removed successfully!", name),
+        }
     } else if command_name == "exray_len" {
         match exray_len(&words, &exrays) {
-            Err(e) => println!("{:?}", e),
+            Err(e) => println!("{}", e),
             Ok((name, len)) => println!("Length of exray {} is {}", name, len),
         }
     } else {
-        println!("No command with that name, command names are - exit, create, save, exray_names, print, get_element, change_element, insert_element, erase_element, erase_segment, extract_segment, insert_exray, clone_segment, segment_fvalues, exray_fvalues, exray_len, country_segment");
+        println!("No command with that name, command names are - exit, create, save, exray_names, print, get_element, change_element, insert_element, erase_element, erase_segment, extract_segment, insert_exray, clone_segment, segment_fvalues, exray_fvalues, add_function, remove_function, exray_len, country_segment");
     }
 }
 
@@ -2224,7 +2497,7 @@ fn main() {
         let option = line.unwrap();
         if option == String::from("default") {
             let mut exrays = HashMap::<String, Exray<i64, i64>>::new();
-            let mut functions = HashMap::<String, Func<i64, i64>>::new();
+            let mut functions = FunctionRegistry::<Func<i64, i64>>::new();
             fill_functions_i64(&mut functions);
             while let Some(line) = line_it.next() {
                 if line.is_err() {
@@ -2253,21 +2526,17 @@ fn main() {
         else if option == String::from("demo") {
             let data = match get_data() {
                 Err(e) => {
-                    println!("{:?}", e);
+                    println!("{}", e);
                     return;
                 }
                 Ok(v) => v,
             };
-            let mut exrays = HashMap::<String, Exray<Element, (f64, f64)>>::new();
-            let mut functions = HashMap::<String, Func<Element, (f64, f64)>>::new();
+            let mut exrays = HashMap::<String, Exray<Element, ElementValues>>::new();
+            let mut functions = FunctionRegistry::<Func<Element, ElementValues>>::new();
             fill_functions_element(&mut functions);
-            let mut functions_vec = Vec::<Func<Element, (f64, f64)>>::new();
-            for (_, func) in &functions {
-                functions_vec.push(*func);
-            }
             exrays.insert(
                 String::from("corona"),
-                Exray::<Element, (f64, f64)>::new(data, functions_vec),
+                Exray::<Element, ElementValues>::with_aggregate(data, element_aggregate()),
             );
             println!("Data stored in exray with name corona!");
             